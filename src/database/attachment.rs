@@ -0,0 +1,165 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::bson::Bson;
+use mongodb::error::Result;
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::attachment::Attachment;
+
+use super::{
+    macros::{basic_create, basic_fetch, eq, eq_keyed, id, keyed},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseAttachment {
+    pub _id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: u64,
+    pub uploader_id: String,
+    pub message_id: Option<String>,
+    pub channel_id: Option<String>,
+}
+
+impl From<&Attachment> for DatabaseAttachment {
+    fn from(value: &Attachment) -> Self {
+        Self {
+            _id: value.id.clone(),
+            filename: value.filename.clone(),
+            content_type: value.content_type.clone(),
+            size: value.size,
+            uploader_id: value.uploader_id.clone(),
+            message_id: None,
+            channel_id: None,
+        }
+    }
+}
+
+impl From<DatabaseAttachment> for Attachment {
+    fn from(value: DatabaseAttachment) -> Self {
+        Self {
+            id: value._id,
+            filename: value.filename,
+            content_type: value.content_type,
+            size: value.size,
+            uploader_id: value.uploader_id,
+        }
+    }
+}
+
+impl Database {
+    pub async fn create_attachment(&self, attachment: Attachment) -> Result<Attachment> {
+        basic_create!(self.attachments, DatabaseAttachment::from, attachment)
+    }
+
+    /// Fetches an attachment and the channel it was sent in, if it has been sent.
+    pub async fn fetch_attachment(&self, id: &str) -> Result<Option<(Attachment, Option<String>)>> {
+        let Some(attachment): Option<DatabaseAttachment> = basic_fetch!(self.attachments, id!(id))?
+        else {
+            return Ok(None);
+        };
+        let channel_id = attachment.channel_id.clone();
+        Ok(Some((attachment.into(), channel_id)))
+    }
+
+    pub async fn fetch_unsent_attachments(
+        &self,
+        ids: Vec<String>,
+        uploader_id: String,
+    ) -> Result<Vec<Attachment>> {
+        let attachments: Vec<DatabaseAttachment> = to_vec(
+            self.attachments
+                .find(eq_keyed!("_id", keyed!("$in", ids), uploader_id), None)
+                .await?,
+        )
+        .await?;
+
+        Ok(attachments
+            .into_iter()
+            .filter(|it| it.message_id.is_none())
+            .map(Into::into)
+            .collect())
+    }
+
+    /// Attaches unsent attachments of an uploader to a message, returning how many were attached.
+    ///
+    /// Attachments that were sent in the meantime are left alone, so each one is only ever
+    /// attached to a single message.
+    pub async fn attach_attachments(
+        &self,
+        ids: Vec<String>,
+        uploader_id: &str,
+        message_id: &str,
+        channel_id: &str,
+    ) -> Result<u64> {
+        Ok(self
+            .attachments
+            .update_many(
+                keyed!(
+                    "_id",
+                    keyed!("$in", ids),
+                    "uploader_id",
+                    uploader_id,
+                    "message_id",
+                    Bson::Null
+                ),
+                keyed!(
+                    "$set",
+                    keyed!("message_id", message_id, "channel_id", channel_id)
+                ),
+                None,
+            )
+            .await?
+            .modified_count)
+    }
+
+    /// Makes the attachments of a message unsent again, if it could not be sent.
+    pub async fn detach_attachments(&self, message_id: &str) -> Result<()> {
+        self.attachments
+            .update_many(
+                eq!(message_id),
+                keyed!(
+                    "$set",
+                    keyed!("message_id", Bson::Null, "channel_id", Bson::Null)
+                ),
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Fetches attachments that were uploaded before `before`, a ULID, and never sent.
+    pub async fn fetch_stale_attachments(&self, before: &str, max: i64) -> Result<Vec<Attachment>> {
+        let filter = keyed!("_id", keyed!("$lt", before), "message_id", Bson::Null);
+
+        let attachments: Vec<DatabaseAttachment> = to_vec(
+            self.attachments
+                .find(filter, FindOptions::builder().limit(max).build())
+                .await?,
+        )
+        .await?;
+
+        Ok(attachments.into_iter().map(Into::into).collect())
+    }
+
+    /// Deletes an attachment unless it has been sent, returning whether it was deleted.
+    pub async fn delete_unsent_attachment(&self, id: &str) -> Result<bool> {
+        Ok(self
+            .attachments
+            .delete_one(keyed!("_id", id, "message_id", Bson::Null), None)
+            .await?
+            .deleted_count
+            > 0)
+    }
+
+    pub async fn delete_attachments(&self, ids: Vec<String>) -> Result<()> {
+        self.attachments
+            .delete_many(keyed!("_id", keyed!("$in", ids)), None)
+            .await?;
+        Ok(())
+    }
+}
@@ -2,13 +2,22 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use mongodb::bson::{to_bson, Bson, DateTime, Document};
 use mongodb::error::Result;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
 use serde::{Deserialize, Serialize};
 
-use crate::structures::channel::{Channel, ChannelLocation};
+use crate::structures::{
+    channel::{Channel, ChannelKind, ChannelLocation, ChannelUpdate, ThreadMetadata},
+    restricted_string::RestrictedString,
+    role::PermissionOverwrite,
+    validation::Field,
+};
 
 use super::{
-    macros::{basic_create, basic_fetch, id},
+    macros::{basic_create, basic_fetch, eq, id, keyed},
+    to_vec,
+    user::DatabaseUser,
     Database,
 };
 
@@ -17,6 +26,28 @@ pub struct DatabaseChannel {
     pub _id: String,
     pub name: String,
     pub location: ChannelLocation,
+    #[serde(default)]
+    pub thread: Option<DatabaseThread>,
+    #[serde(default)]
+    pub encrypted: bool,
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub position: u32,
+    #[serde(default)]
+    pub kind: ChannelKind,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub overwrites: Vec<PermissionOverwrite>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DatabaseThread {
+    pub creator_id: String,
+    pub archived: bool,
+    pub auto_archive_after: u64,
+    pub archive_at: DateTime,
 }
 
 impl From<&Channel> for DatabaseChannel {
@@ -25,6 +56,18 @@ impl From<&Channel> for DatabaseChannel {
             _id: value.id.to_string(),
             name: value.name.clone(),
             location: value.location.clone(),
+            thread: value.thread.as_ref().map(|it| DatabaseThread {
+                creator_id: it.creator_id.clone(),
+                archived: it.archived,
+                auto_archive_after: it.auto_archive_after,
+                archive_at: DateTime::parse_rfc3339_str(&it.archive_at).unwrap(),
+            }),
+            encrypted: value.encrypted,
+            topic: value.topic.clone(),
+            position: value.position,
+            kind: value.kind,
+            category: value.category.clone(),
+            overwrites: value.overwrites.clone(),
         }
     }
 }
@@ -35,6 +78,18 @@ impl From<DatabaseChannel> for Channel {
             id: value._id,
             name: value.name,
             location: value.location,
+            thread: value.thread.map(|it| ThreadMetadata {
+                creator_id: it.creator_id,
+                archived: it.archived,
+                auto_archive_after: it.auto_archive_after,
+                archive_at: it.archive_at.try_to_rfc3339_string().unwrap(),
+            }),
+            encrypted: value.encrypted,
+            topic: value.topic,
+            position: value.position,
+            kind: value.kind,
+            category: value.category,
+            overwrites: value.overwrites,
         }
     }
 }
@@ -47,4 +102,237 @@ impl Database {
     pub async fn fetch_channel(&self, id: String) -> Result<Option<Channel>> {
         basic_fetch!(self.channels, id!(id))
     }
+
+    /// Fetches the DM channels of a user and the channels and threads of the guilds they are in.
+    pub async fn fetch_user_channels(&self, user: &str) -> Result<Vec<Channel>> {
+        let Some(user): Option<DatabaseUser> = basic_fetch!(self.users, id!(user))? else {
+            return Ok(vec![])
+        };
+
+        let filter = keyed!(
+            "$or",
+            vec![
+                Bson::from(keyed!("location.type", "dm", "location.members", &user._id)),
+                Bson::from(keyed!(
+                    "location.type",
+                    "guild",
+                    "location.id",
+                    keyed!("$in", &user.guilds)
+                )),
+            ]
+        );
+
+        let mut channels = to_vec(self.channels.find(filter, None).await?).await?;
+
+        let parents: Vec<String> = channels
+            .iter()
+            .filter(|it| matches!(it.location, ChannelLocation::Guild { .. }))
+            .map(|it| it._id.clone())
+            .collect();
+
+        let threads = keyed!(
+            "location.type",
+            "thread",
+            "location.parent",
+            keyed!("$in", parents)
+        );
+
+        channels.extend(to_vec(self.channels.find(threads, None).await?).await?);
+
+        Ok(channels.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn fetch_thread_by_origin(&self, origin: &str) -> Result<Option<Channel>> {
+        basic_fetch!(
+            self.channels,
+            keyed!("location.type", "thread", "location.origin", origin)
+        )
+    }
+
+    /// Archives the threads started from any of the given messages, returning them.
+    pub async fn archive_threads_of(&self, origins: Vec<String>) -> Result<Vec<Channel>> {
+        let filter = keyed!(
+            "location.type",
+            "thread",
+            "location.origin",
+            keyed!("$in", origins),
+            "thread.archived",
+            false
+        );
+
+        let threads: Vec<Channel> = to_vec(self.channels.find(filter, None).await?)
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect();
+
+        let mut archived = vec![];
+        for thread in threads {
+            let filter = keyed!("_id", thread.id, "thread.archived", false);
+            if let Some(thread) = self
+                .set_channel_fields(filter, keyed!("thread.archived", true))
+                .await?
+            {
+                archived.push(thread);
+            }
+        }
+        Ok(archived)
+    }
+
+    /// Fetches all threads of a channel, archived or not.
+    pub async fn fetch_threads(&self, parent: &str) -> Result<Vec<Channel>> {
+        let filter = keyed!("location.type", "thread", "location.parent", parent);
+
+        let threads = to_vec(self.channels.find(filter, None).await?).await?;
+
+        Ok(threads.into_iter().map(Into::into).collect())
+    }
+
+    /// Fetches the threads of a channel that are not archived.
+    pub async fn fetch_active_threads(&self, parent: &str) -> Result<Vec<Channel>> {
+        let filter = keyed!(
+            "location.type",
+            "thread",
+            "location.parent",
+            parent,
+            "thread.archived",
+            false
+        );
+
+        let threads = to_vec(self.channels.find(filter, None).await?).await?;
+
+        Ok(threads.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn fetch_threads_to_archive(&self) -> Result<Vec<Channel>> {
+        let filter = keyed!(
+            "thread.archived",
+            false,
+            "thread.archive_at",
+            keyed!("$lte", DateTime::now())
+        );
+
+        let threads = to_vec(self.channels.find(filter, None).await?).await?;
+
+        Ok(threads.into_iter().map(Into::into).collect())
+    }
+
+    /// Archives a thread if it is still inactive.
+    pub async fn archive_thread(&self, id: String) -> Result<Option<Channel>> {
+        let filter = keyed!(
+            "_id",
+            id,
+            "thread.archived",
+            false,
+            "thread.archive_at",
+            keyed!("$lte", DateTime::now())
+        );
+
+        self.set_channel_fields(filter, keyed!("thread.archived", true))
+            .await
+    }
+
+    /// Marks a thread as active now, unarchiving it if needed.
+    pub async fn touch_thread(&self, id: String, archive_at: String) -> Result<Option<Channel>> {
+        let archive_at = DateTime::parse_rfc3339_str(archive_at).unwrap();
+
+        self.set_channel_fields(
+            id!(id),
+            keyed!("thread.archived", false, "thread.archive_at", archive_at),
+        )
+        .await
+    }
+
+    /// Renames a channel or changes its topic or position.
+    pub async fn update_channel(
+        &self,
+        id: String,
+        update: &ChannelUpdate,
+    ) -> Result<Option<Channel>> {
+        let mut set = Document::new();
+        if let Some(name) = &update.name {
+            set.insert("name", RestrictedString::no_space(name, Field::ChannelName));
+        }
+        if let Some(topic) = &update.topic {
+            let topic = Some(topic).filter(|it| !it.is_empty());
+            set.insert("topic", topic);
+        }
+        if let Some(position) = update.position {
+            set.insert("position", position);
+        }
+
+        if set.is_empty() {
+            return self.fetch_channel(id).await;
+        }
+
+        self.set_channel_fields(id!(id), set).await
+    }
+
+    pub async fn set_channel_overwrites(
+        &self,
+        id: &str,
+        overwrites: &[PermissionOverwrite],
+    ) -> Result<Option<Channel>> {
+        self.set_channel_fields(id!(id), keyed!("overwrites", to_bson(overwrites)?))
+            .await
+    }
+
+    /// Fetches the channels of a guild, without their threads.
+    pub async fn fetch_guild_channels(&self, guild: &str) -> Result<Vec<Channel>> {
+        let filter = keyed!("location.type", "guild", "location.id", guild);
+
+        let channels = to_vec(self.channels.find(filter, None).await?).await?;
+
+        Ok(channels.into_iter().map(Into::into).collect())
+    }
+
+    /// Moves a guild channel, returning it if it changed.
+    pub async fn set_channel_position(
+        &self,
+        id: &str,
+        position: u32,
+        category: Option<String>,
+    ) -> Result<Option<Channel>> {
+        let filter = keyed!(
+            "_id",
+            id,
+            "$or",
+            vec![
+                keyed!("position", keyed!("$ne", position)),
+                keyed!("category", keyed!("$ne", category.clone())),
+            ]
+        );
+
+        self.set_channel_fields(filter, keyed!("position", position, "category", category))
+            .await
+    }
+
+    /// Moves the channels of a deleted category to the top level.
+    pub async fn clear_category(&self, category: &str) -> Result<()> {
+        self.channels
+            .update_many(
+                eq!(category),
+                keyed!("$set", keyed!("category", Bson::Null)),
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_channel(&self, id: &str) -> Result<bool> {
+        Ok(self.channels.delete_one(id!(id), None).await?.deleted_count > 0)
+    }
+
+    async fn set_channel_fields(&self, filter: Document, set: Document) -> Result<Option<Channel>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let channel = self
+            .channels
+            .find_one_and_update(filter, keyed!("$set", set), options)
+            .await?;
+
+        Ok(channel.map(Into::into))
+    }
 }
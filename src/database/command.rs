@@ -0,0 +1,95 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Result;
+use mongodb::options::ReplaceOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::command::{Command, CommandOption};
+
+use super::{
+    macros::{basic_fetch, eq, eq_keyed, id, keyed},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseCommand {
+    pub _id: String,
+    pub guild_id: String,
+    pub bot_id: String,
+    pub name: String,
+    pub description: String,
+    pub options: Vec<CommandOption>,
+}
+
+impl From<&Command> for DatabaseCommand {
+    fn from(value: &Command) -> Self {
+        Self {
+            _id: value.id.clone(),
+            guild_id: value.guild_id.clone(),
+            bot_id: value.bot_id.clone(),
+            name: value.name.clone(),
+            description: value.description.clone(),
+            options: value.options.clone(),
+        }
+    }
+}
+
+impl From<DatabaseCommand> for Command {
+    fn from(value: DatabaseCommand) -> Self {
+        Self {
+            id: value._id,
+            guild_id: value.guild_id,
+            bot_id: value.bot_id,
+            name: value.name,
+            description: value.description,
+            options: value.options,
+        }
+    }
+}
+
+impl Database {
+    /// Registers a command, replacing the bot's command with the same name in the guild.
+    pub async fn upsert_command(&self, mut command: Command) -> Result<Command> {
+        let (guild_id, bot_id, name) = (&command.guild_id, &command.bot_id, &command.name);
+        if let Some(existing) = self
+            .commands
+            .find_one(eq!(guild_id, bot_id, name), None)
+            .await?
+        {
+            command.id = existing._id;
+        }
+
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.commands
+            .replace_one(id!(&command.id), DatabaseCommand::from(&command), options)
+            .await?;
+
+        Ok(command)
+    }
+
+    pub async fn fetch_command(&self, id: &str) -> Result<Option<Command>> {
+        basic_fetch!(self.commands, id!(id))
+    }
+
+    pub async fn fetch_guild_commands(&self, guild_id: &str) -> Result<Vec<Command>> {
+        let commands = to_vec(self.commands.find(eq!(guild_id), None).await?).await?;
+        Ok(commands.into_iter().map(Into::into).collect())
+    }
+
+    /// Counts the commands a bot has in a guild, other than the one named `name`.
+    pub async fn count_other_bot_commands(
+        &self,
+        guild_id: &str,
+        bot_id: &str,
+        name: &str,
+    ) -> Result<u64> {
+        let filter = eq_keyed!("name", keyed!("$ne", name), guild_id, bot_id);
+        self.commands.count_documents(filter, None).await
+    }
+
+    pub async fn delete_command(&self, id: &str) -> Result<bool> {
+        Ok(self.commands.delete_one(id!(id), None).await?.deleted_count > 0)
+    }
+}
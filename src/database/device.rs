@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::bson::{to_bson, DateTime};
+use mongodb::error::Result;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::structures::encryption::{Device, PreKey, SignedPreKey, MAX_ONE_TIME_PREKEYS};
+
+use super::{
+    macros::{basic_fetch, eq, id, keyed},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseDevice {
+    pub _id: String,
+    pub user_id: String,
+    pub name: String,
+    pub identity_key: String,
+    pub signed_prekey: SignedPreKey,
+    /// Claimed from the front, so the oldest keys are handed out first.
+    pub one_time_prekeys: Vec<PreKey>,
+    pub created: DateTime,
+}
+
+impl From<DatabaseDevice> for Device {
+    fn from(value: DatabaseDevice) -> Self {
+        Self {
+            id: value._id,
+            user_id: value.user_id,
+            name: value.name,
+            identity_key: value.identity_key,
+            signed_prekey: value.signed_prekey,
+            one_time_prekeys: value.one_time_prekeys.len(),
+            created: value.created.try_to_rfc3339_string().unwrap(),
+        }
+    }
+}
+
+impl Database {
+    pub async fn create_device(
+        &self,
+        device: Device,
+        mut one_time_prekeys: Vec<PreKey>,
+    ) -> Result<Device> {
+        let excess = one_time_prekeys.len().saturating_sub(MAX_ONE_TIME_PREKEYS);
+        one_time_prekeys.drain(..excess);
+
+        let device = Device {
+            one_time_prekeys: one_time_prekeys.len(),
+            ..device
+        };
+
+        self.devices
+            .insert_one(
+                DatabaseDevice {
+                    _id: device.id.clone(),
+                    user_id: device.user_id.clone(),
+                    name: device.name.clone(),
+                    identity_key: device.identity_key.clone(),
+                    signed_prekey: device.signed_prekey.clone(),
+                    one_time_prekeys,
+                    created: DateTime::parse_rfc3339_str(&device.created).unwrap(),
+                },
+                None,
+            )
+            .await?;
+
+        Ok(device)
+    }
+
+    pub async fn fetch_device(&self, id: &str) -> Result<Option<Device>> {
+        basic_fetch!(self.devices, id!(id))
+    }
+
+    pub async fn fetch_user_devices(&self, user_id: &str) -> Result<Vec<Device>> {
+        let devices = to_vec(self.devices.find(eq!(user_id), None).await?).await?;
+        Ok(devices.into_iter().map(Into::into).collect())
+    }
+
+    /// Fetches the devices of all of the users.
+    pub async fn fetch_devices_of(&self, users: &[String]) -> Result<Vec<Device>> {
+        let filter = keyed!("user_id", keyed!("$in", users));
+        let devices = to_vec(self.devices.find(filter, None).await?).await?;
+        Ok(devices.into_iter().map(Into::into).collect())
+    }
+
+    /// Rotates the signed prekey and appends one-time prekeys, keeping the newest ones.
+    pub async fn add_prekeys(
+        &self,
+        id: &str,
+        signed_prekey: Option<SignedPreKey>,
+        one_time_prekeys: Vec<PreKey>,
+    ) -> Result<Option<Device>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let mut update = keyed!(
+            "$push",
+            keyed!(
+                "one_time_prekeys",
+                keyed!(
+                    "$each",
+                    to_bson(&one_time_prekeys)?,
+                    "$slice",
+                    -(MAX_ONE_TIME_PREKEYS as i64)
+                )
+            )
+        );
+        if let Some(signed_prekey) = signed_prekey {
+            update.insert("$set", keyed!("signed_prekey", to_bson(&signed_prekey)?));
+        }
+
+        Ok(self
+            .devices
+            .find_one_and_update(id!(id), update, options)
+            .await?
+            .map(Into::into))
+    }
+
+    /// Takes the oldest one-time prekey of a device, so it is never handed out twice.
+    pub async fn claim_prekey(&self, id: &str) -> Result<Option<PreKey>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::Before)
+            .build();
+
+        let mut filter = id!(id);
+        filter.insert("one_time_prekeys.0", keyed!("$exists", true));
+
+        let device = self
+            .devices
+            .find_one_and_update(
+                filter,
+                keyed!("$pop", keyed!("one_time_prekeys", -1)),
+                options,
+            )
+            .await?;
+
+        Ok(device.and_then(|it| it.one_time_prekeys.into_iter().next()))
+    }
+
+    pub async fn delete_device(&self, id: &str) -> Result<bool> {
+        Ok(self.devices.delete_one(id!(id), None).await?.deleted_count > 0)
+    }
+}
@@ -0,0 +1,65 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::emoji::GuildEmoji;
+
+use super::{
+    macros::{basic_create, basic_fetch, eq, id},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseEmoji {
+    pub _id: String,
+    pub guild_id: String,
+    pub name: String,
+    pub content_type: String,
+    pub creator_id: String,
+}
+
+impl From<&GuildEmoji> for DatabaseEmoji {
+    fn from(value: &GuildEmoji) -> Self {
+        Self {
+            _id: value.id.clone(),
+            guild_id: value.guild_id.clone(),
+            name: value.name.clone(),
+            content_type: value.content_type.clone(),
+            creator_id: value.creator_id.clone(),
+        }
+    }
+}
+
+impl From<DatabaseEmoji> for GuildEmoji {
+    fn from(value: DatabaseEmoji) -> Self {
+        Self {
+            id: value._id,
+            guild_id: value.guild_id,
+            name: value.name,
+            content_type: value.content_type,
+            creator_id: value.creator_id,
+        }
+    }
+}
+
+impl Database {
+    pub async fn create_emoji(&self, emoji: GuildEmoji) -> Result<GuildEmoji> {
+        basic_create!(self.emojis, DatabaseEmoji::from, emoji)
+    }
+
+    pub async fn fetch_emoji(&self, id: &str) -> Result<Option<GuildEmoji>> {
+        basic_fetch!(self.emojis, id!(id))
+    }
+
+    pub async fn fetch_guild_emojis(&self, guild_id: &str) -> Result<Vec<GuildEmoji>> {
+        let emojis = to_vec(self.emojis.find(eq!(guild_id), None).await?).await?;
+        Ok(emojis.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn delete_emoji(&self, id: &str) -> Result<bool> {
+        Ok(self.emojis.delete_one(id!(id), None).await?.deleted_count > 0)
+    }
+}
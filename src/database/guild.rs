@@ -119,6 +119,14 @@ impl Database {
         Ok(DatabaseGuildResponse::Ok(guilds))
     }
 
+    pub async fn is_guild_member(&self, id: &str, user: &str) -> Result<bool> {
+        Ok(self
+            .users
+            .find_one(keyed!("_id", user, "guilds", id), None)
+            .await?
+            .is_some())
+    }
+
     pub async fn join_guild(&self, id: &str, user: &str) -> Result<DatabaseGuildResponse<User>> {
         if self.fetch_guild(id).await?.is_none() {
             return Ok(DatabaseGuildResponse::NoGuild);
@@ -131,7 +139,11 @@ impl Database {
         if !user.guilds.contains(&id) {
             user.guilds.push(id);
             self.users
-                .find_one_and_update(id!(&user._id), keyed!("guilds", &user.guilds), None)
+                .find_one_and_update(
+                    id!(&user._id),
+                    keyed!("$set", keyed!("guilds", &user.guilds)),
+                    None,
+                )
                 .await?;
         }
 
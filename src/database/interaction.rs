@@ -0,0 +1,125 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use mongodb::bson::DateTime;
+use mongodb::error::Result;
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::{
+    interaction::{Interaction, InteractionData},
+    user::User,
+};
+
+use super::{
+    macros::{eq_keyed, id, keyed},
+    Database,
+};
+
+/// How long a bot has to respond to an interaction.
+pub const INTERACTION_WINDOW: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseInteraction {
+    pub _id: String,
+    pub channel_id: String,
+    pub bot_id: String,
+    pub user: User,
+    pub data: InteractionData,
+    pub created: DateTime,
+    pub responded: bool,
+}
+
+impl From<&Interaction> for DatabaseInteraction {
+    fn from(value: &Interaction) -> Self {
+        Self {
+            _id: value.id.clone(),
+            channel_id: value.channel_id.clone(),
+            bot_id: value.bot_id.clone(),
+            user: value.user.clone(),
+            data: value.data.clone(),
+            created: DateTime::parse_rfc3339_str(&value.created).unwrap(),
+            responded: false,
+        }
+    }
+}
+
+impl From<DatabaseInteraction> for Interaction {
+    fn from(value: DatabaseInteraction) -> Self {
+        Self {
+            id: value._id,
+            channel_id: value.channel_id,
+            bot_id: value.bot_id,
+            user: value.user,
+            data: value.data,
+            created: value.created.try_to_rfc3339_string().unwrap(),
+        }
+    }
+}
+
+fn window_start() -> DateTime {
+    DateTime::from_millis(
+        DateTime::now().timestamp_millis() - INTERACTION_WINDOW.as_millis() as i64,
+    )
+}
+
+impl Database {
+    pub(super) async fn create_interaction_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(keyed!("created", 1))
+            .options(
+                IndexOptions::builder()
+                    .expire_after(INTERACTION_WINDOW)
+                    .build(),
+            )
+            .build();
+        self.interactions.create_index(index, None).await?;
+        Ok(())
+    }
+
+    pub async fn create_interaction(&self, interaction: Interaction) -> Result<Interaction> {
+        self.interactions
+            .insert_one(DatabaseInteraction::from(&interaction), None)
+            .await?;
+        Ok(interaction)
+    }
+
+    /// Fetches an interaction that can still be responded to.
+    pub async fn fetch_interaction(&self, id: &str) -> Result<Option<Interaction>> {
+        let created = keyed!("$gte", window_start());
+        let responded = false;
+        Ok(self
+            .interactions
+            .find_one(eq_keyed!("_id", id, created, responded), None)
+            .await?
+            .map(Into::into))
+    }
+
+    /// Marks an interaction as responded to, returning whether it had not been yet.
+    pub async fn claim_interaction(&self, id: &str) -> Result<bool> {
+        let created = keyed!("$gte", window_start());
+        let responded = false;
+        Ok(self
+            .interactions
+            .update_one(
+                eq_keyed!("_id", id, created, responded),
+                keyed!("$set", keyed!("responded", true)),
+                None,
+            )
+            .await?
+            .modified_count
+            > 0)
+    }
+
+    /// Lets an interaction be responded to again, if its response could not be sent.
+    pub async fn release_interaction(&self, id: &str) -> Result<()> {
+        self.interactions
+            .update_one(id!(id), keyed!("$set", keyed!("responded", false)), None)
+            .await?;
+        Ok(())
+    }
+}
@@ -57,10 +57,12 @@ pub(super) macro id($id: expr $(, $($val: expr),*)?) {
     eq_keyed!("_id", $id $(, $($val,)*)?)
 }
 
+/// Matches documents created before `$time`.
 pub(super) macro before($time: expr $(, $($val: expr),*)?) {
-    eq_keyed!("created", keyed!("lt", $time) $(, $($val),*)?)
+    eq_keyed!("created", keyed!("$lt", $time) $(, $($val),*)?)
 }
 
+/// Matches documents created after `$time`.
 pub(super) macro after($time: expr $(, $($val: expr),*)?) {
-    eq_keyed!("created", keyed!("gt", $time) $(, $($val),*)?)
+    eq_keyed!("created", keyed!("$gt", $time) $(, $($val),*)?)
 }
@@ -0,0 +1,78 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::bson::DateTime;
+use mongodb::error::Result;
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::{generate_ulid, structures::message::Message};
+
+use super::{
+    macros::{before, keyed},
+    to_vec, Database,
+};
+
+/// An entry in a user's mentions inbox.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseMention {
+    pub _id: String,
+    pub user_id: String,
+    pub message_id: String,
+    pub channel_id: String,
+    pub created: DateTime,
+}
+
+impl Database {
+    pub async fn create_mentions(&self, message: &Message, users: &[String]) -> Result<()> {
+        if users.is_empty() {
+            return Ok(());
+        }
+
+        let created = DateTime::parse_rfc3339_str(&message.created).unwrap();
+        let mentions = users.iter().map(|user_id| DatabaseMention {
+            _id: generate_ulid(),
+            user_id: user_id.clone(),
+            message_id: message.id.clone(),
+            channel_id: message.channel_id.clone(),
+            created,
+        });
+
+        self.mentions.insert_many(mentions, None).await?;
+        Ok(())
+    }
+
+    pub async fn delete_message_mentions(&self, message_ids: Vec<String>) -> Result<()> {
+        self.mentions
+            .delete_many(keyed!("message_id", keyed!("$in", message_ids)), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Fetches the ids of the messages a user was mentioned in, newest first.
+    pub async fn fetch_mentions_before(
+        &self,
+        user_id: String,
+        time: String,
+        max: i64,
+    ) -> Result<Vec<String>> {
+        let Ok(timestamp) = DateTime::parse_rfc3339_str(time) else {
+            return Ok(vec![]);
+        };
+
+        let options = FindOptions::builder()
+            .limit(max)
+            .sort(keyed!("created", -1))
+            .build();
+
+        let mentions = to_vec(
+            self.mentions
+                .find(before!(timestamp, user_id), options)
+                .await?,
+        )
+        .await?;
+
+        Ok(mentions.into_iter().map(|it| it.message_id).collect())
+    }
+}
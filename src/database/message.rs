@@ -2,15 +2,26 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use mongodb::bson::{doc, DateTime};
+use mongodb::bson::{doc, Bson, DateTime, Document};
 use mongodb::error::Result;
-use mongodb::options::FindOptions;
+use mongodb::bson::to_bson;
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument};
+use mongodb::IndexModel;
 use serde::{Deserialize, Serialize};
 
-use crate::structures::message::Message;
+use crate::structures::{
+    attachment::Attachment,
+    component::ActionRow,
+    embed::Embed,
+    encryption::EncryptedContent,
+    interaction::MessageInteraction,
+    mention::Mentions,
+    message::{Message, MessageSearch},
+    poll::Poll,
+};
 
 use super::{
-    macros::{after, basic_create, basic_fetch, before, id, keyed},
+    macros::{after, basic_create, basic_fetch, before, eq, eq_keyed, id, keyed},
     to_vec, Database,
 };
 
@@ -21,6 +32,60 @@ pub struct DatabaseMessage {
     pub author_id: Option<String>,
     pub content: String,
     pub created: DateTime,
+    pub reply_to: Option<String>,
+    #[serde(default)]
+    pub mentions: Mentions,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
+    pub pinned_at: Option<DateTime>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub expires: Option<DateTime>,
+    #[serde(default)]
+    pub poll: Option<DatabasePoll>,
+    #[serde(default)]
+    pub interaction: Option<MessageInteraction>,
+    #[serde(default)]
+    pub components: Vec<ActionRow>,
+    #[serde(default)]
+    pub encrypted: Option<EncryptedContent>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DatabasePoll {
+    pub question: String,
+    pub options: Vec<String>,
+    pub multi_select: bool,
+    pub closes_at: DateTime,
+    pub results: Option<Vec<u64>>,
+}
+
+impl From<&Poll> for DatabasePoll {
+    fn from(value: &Poll) -> Self {
+        Self {
+            question: value.question.clone(),
+            options: value.options.clone(),
+            multi_select: value.multi_select,
+            closes_at: DateTime::parse_rfc3339_str(&value.closes_at).unwrap(),
+            results: value.results.clone(),
+        }
+    }
+}
+
+impl From<DatabasePoll> for Poll {
+    fn from(value: DatabasePoll) -> Self {
+        Self {
+            question: value.question,
+            options: value.options,
+            multi_select: value.multi_select,
+            closes_at: value.closes_at.try_to_rfc3339_string().unwrap(),
+            results: value.results,
+        }
+    }
 }
 
 impl From<&Message> for DatabaseMessage {
@@ -31,6 +96,23 @@ impl From<&Message> for DatabaseMessage {
             author_id: value.author_id.clone(),
             content: value.content.to_string(),
             created: DateTime::parse_rfc3339_str(value.created.clone()).unwrap(),
+            reply_to: value.reply_to.clone(),
+            mentions: value.mentions.clone(),
+            attachments: value.attachments.clone(),
+            embeds: value.embeds.clone(),
+            pinned_at: value
+                .pinned_at
+                .as_ref()
+                .map(|it| DateTime::parse_rfc3339_str(it).unwrap()),
+            nonce: value.nonce.clone(),
+            expires: value
+                .expires
+                .as_ref()
+                .map(|it| DateTime::parse_rfc3339_str(it).unwrap()),
+            poll: value.poll.as_ref().map(DatabasePoll::from),
+            interaction: value.interaction.clone(),
+            components: value.components.clone(),
+            encrypted: value.encrypted.clone(),
         }
     }
 }
@@ -43,17 +125,182 @@ impl From<DatabaseMessage> for Message {
             author_id: value.author_id,
             content: value.content,
             created: value.created.try_to_rfc3339_string().unwrap(),
+            reply_to: value.reply_to,
+            mentions: value.mentions,
+            attachments: value.attachments,
+            embeds: value.embeds,
+            pinned_at: value
+                .pinned_at
+                .map(|it| it.try_to_rfc3339_string().unwrap()),
+            nonce: value.nonce,
+            expires: value.expires.map(|it| it.try_to_rfc3339_string().unwrap()),
+            poll: value.poll.map(Into::into),
+            interaction: value.interaction,
+            components: value.components,
+            encrypted: value.encrypted,
         }
     }
 }
 
+/// Excludes messages that have expired but may not have been swept yet.
+fn unexpired(mut filter: Document) -> Document {
+    filter.insert(
+        "$or",
+        vec![
+            keyed!("expires", Bson::Null),
+            keyed!("expires", keyed!("$gt", DateTime::now())),
+        ],
+    );
+    filter
+}
+
 impl Database {
+    pub(super) async fn create_message_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(keyed!("content", "text"))
+            .build();
+        self.messages.create_index(index, None).await?;
+
+        let index = IndexModel::builder()
+            .keys(keyed!("expires", 1))
+            .options(IndexOptions::builder().sparse(true).build())
+            .build();
+        self.messages.create_index(index, None).await?;
+        Ok(())
+    }
+
     pub async fn create_message(&self, message: Message) -> Result<Message> {
         basic_create!(self.messages, DatabaseMessage::from, message)
     }
 
+    /// Inserts many messages at once, such as imported history.
+    pub async fn create_messages(&self, messages: &[Message]) -> Result<()> {
+        if messages.is_empty() {
+            return Ok(());
+        }
+        self.messages
+            .insert_many(messages.iter().map(DatabaseMessage::from), None)
+            .await?;
+        Ok(())
+    }
+
     pub async fn fetch_message(&self, id: String) -> Result<Option<Message>> {
-        basic_fetch!(self.messages, id!(id))
+        basic_fetch!(self.messages, unexpired(id!(id)))
+    }
+
+    /// Adds embeds after those a message already has.
+    pub async fn push_message_embeds(
+        &self,
+        id: String,
+        embeds: Vec<Embed>,
+    ) -> Result<Option<Message>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let message = self
+            .messages
+            .find_one_and_update(
+                id!(id),
+                keyed!(
+                    "$push",
+                    keyed!("embeds", keyed!("$each", to_bson(&embeds)?))
+                ),
+                options,
+            )
+            .await?;
+
+        Ok(message.map(Into::into))
+    }
+
+    pub async fn set_message_pinned(
+        &self,
+        id: String,
+        pinned_at: Option<String>,
+    ) -> Result<Option<Message>> {
+        let pinned_at = pinned_at.map(|it| DateTime::parse_rfc3339_str(it).unwrap());
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let message = self
+            .messages
+            .find_one_and_update(
+                id!(id),
+                keyed!("$set", keyed!("pinned_at", pinned_at)),
+                options,
+            )
+            .await?;
+
+        Ok(message.map(Into::into))
+    }
+
+    pub async fn fetch_pinned_messages(&self, channel_id: String) -> Result<Vec<Message>> {
+        let options = FindOptions::builder()
+            .sort(keyed!("pinned_at", -1))
+            .build();
+
+        let messages = to_vec(
+            self.messages
+                .find(
+                    unexpired(eq_keyed!(
+                        "pinned_at",
+                        keyed!("$ne", Bson::Null),
+                        channel_id
+                    )),
+                    options,
+                )
+                .await?,
+        )
+        .await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_pinned_messages(&self, channel_id: String) -> Result<u64> {
+        let filter = eq_keyed!("pinned_at", keyed!("$ne", Bson::Null), channel_id);
+        self.messages.count_documents(unexpired(filter), None).await
+    }
+
+    pub async fn fetch_messages_in(
+        &self,
+        channel_id: String,
+        ids: Vec<String>,
+    ) -> Result<Vec<Message>> {
+        let messages = to_vec(
+            self.messages
+                .find(eq_keyed!("_id", keyed!("$in", ids), channel_id), None)
+                .await?,
+        )
+        .await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn delete_message(&self, id: String) -> Result<bool> {
+        self.delete_message_reactions(vec![id.clone()]).await?;
+        self.delete_message_mentions(vec![id.clone()]).await?;
+        self.delete_message_poll_votes(vec![id.clone()]).await?;
+        Ok(self.messages.delete_one(id!(id), None).await?.deleted_count > 0)
+    }
+
+    pub async fn delete_messages(&self, channel_id: String, ids: Vec<String>) -> Result<u64> {
+        self.delete_message_reactions(ids.clone()).await?;
+        self.delete_message_mentions(ids.clone()).await?;
+        self.delete_message_poll_votes(ids.clone()).await?;
+        Ok(self
+            .messages
+            .delete_many(eq_keyed!("_id", keyed!("$in", ids), channel_id), None)
+            .await?
+            .deleted_count)
+    }
+
+    /// Fetches up to `max` messages of a channel, expired or not, in no particular order.
+    pub async fn fetch_message_batch(&self, channel_id: &str, max: i64) -> Result<Vec<Message>> {
+        let options = FindOptions::builder().limit(max).build();
+        let messages = to_vec(self.messages.find(eq!(channel_id), options).await?).await?;
+        Ok(messages.into_iter().map(Into::into).collect())
     }
 
     pub async fn fetch_messages_before(
@@ -66,7 +313,7 @@ impl Database {
             return Ok(vec![]);
         };
 
-        let Ok(messages) = to_vec(self.messages.find(before!(timestamp, channel_id), FindOptions::builder().limit(max).sort(keyed!("created", -1)).build()).await?).await else {
+        let Ok(messages) = to_vec(self.messages.find(unexpired(before!(timestamp, channel_id)), FindOptions::builder().limit(max).sort(keyed!("created", -1)).build()).await?).await else {
             return Ok(vec![]);
         };
 
@@ -83,10 +330,103 @@ impl Database {
             return Ok(vec![]);
         };
 
-        let Ok(messages) = to_vec(self.messages.find(after!(timestamp, channel_id), FindOptions::builder().limit(max).build()).await?).await else {
+        let Ok(messages) = to_vec(self.messages.find(unexpired(after!(timestamp, channel_id)), FindOptions::builder().limit(max).sort(keyed!("created", 1)).build()).await?).await else {
             return Ok(vec![]);
         };
 
         Ok(messages.into_iter().map(Into::into).collect())
     }
+
+    /// Fetches up to `max` messages of a channel, oldest first, starting after the message with
+    /// the given time and id.
+    ///
+    /// Messages sent in the same millisecond are ordered by id, so none of them fall between
+    /// two pages.
+    pub async fn fetch_transcript_page(
+        &self,
+        channel_id: &str,
+        after: Option<(&str, &str)>,
+        max: i64,
+    ) -> Result<Vec<Message>> {
+        let mut filter = eq!(channel_id);
+        if let Some((time, id)) = after {
+            let Ok(created) = DateTime::parse_rfc3339_str(time) else {
+                return Ok(vec![]);
+            };
+            let cursor = vec![
+                keyed!("created", keyed!("$gt", created)),
+                keyed!("created", created, "_id", keyed!("$gt", id)),
+            ];
+            filter.insert("$and", vec![keyed!("$or", cursor)]);
+        }
+
+        let options = FindOptions::builder()
+            .limit(max)
+            .sort(keyed!("created", 1, "_id", 1))
+            .build();
+        let messages = to_vec(self.messages.find(unexpired(filter), options).await?).await?;
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    /// Searches the text of messages in the given channels, newest first.
+    pub async fn search_messages(
+        &self,
+        search: &MessageSearch,
+        channel_ids: Vec<String>,
+        max: i64,
+    ) -> Result<Vec<Message>> {
+        let mut filter = keyed!(
+            "$text",
+            keyed!("$search", &search.query),
+            "channel_id",
+            keyed!("$in", channel_ids)
+        );
+
+        if let Some(author) = &search.author {
+            filter.insert("author_id", author);
+        }
+
+        let mut created = Document::new();
+        if let Some(before) = &search.before {
+            let Ok(before) = DateTime::parse_rfc3339_str(before) else {
+                return Ok(vec![]);
+            };
+            created.insert("$lt", before);
+        }
+        if let Some(after) = &search.after {
+            let Ok(after) = DateTime::parse_rfc3339_str(after) else {
+                return Ok(vec![]);
+            };
+            created.insert("$gt", after);
+        }
+        if !created.is_empty() {
+            filter.insert("created", created);
+        }
+
+        if let Some(has_attachment) = search.has_attachment {
+            filter.insert("attachments.0", keyed!("$exists", has_attachment));
+        }
+
+        let options = FindOptions::builder()
+            .limit(max)
+            .sort(keyed!("created", -1))
+            .build();
+
+        let messages = to_vec(self.messages.find(unexpired(filter), options).await?).await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn fetch_expired_messages(&self, max: i64) -> Result<Vec<Message>> {
+        let filter = keyed!("expires", keyed!("$lte", DateTime::now()));
+
+        let messages = to_vec(
+            self.messages
+                .find(filter, FindOptions::builder().limit(max).build())
+                .await?,
+        )
+        .await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
 }
@@ -4,11 +4,23 @@
 
 #![allow(clippy::used_underscore_binding)]
 
+mod attachment;
 mod auth;
 mod channel;
+mod command;
+mod device;
+mod emoji;
 mod guild;
+mod interaction;
 mod macros;
+mod mention;
 mod message;
+mod nonce;
+mod poll;
+mod reaction;
+mod read_state;
+mod role;
+mod scheduled_message;
 mod user;
 
 pub use guild::DatabaseGuildResponse;
@@ -16,12 +28,25 @@ pub use guild::DatabaseGuildResponse;
 use futures::stream::TryStreamExt;
 use futures::TryStream;
 use mongodb::Cursor;
+use mongodb::error::{Error, ErrorKind, WriteFailure};
 use mongodb::{error::Result, options::ClientOptions, Client, Collection};
 
+use self::attachment::DatabaseAttachment;
 use self::auth::DatabaseLogin;
 use self::channel::DatabaseChannel;
+use self::command::DatabaseCommand;
+use self::device::DatabaseDevice;
+use self::emoji::DatabaseEmoji;
 use self::guild::DatabaseGuild;
+use self::interaction::DatabaseInteraction;
+use self::mention::DatabaseMention;
 use self::message::DatabaseMessage;
+use self::nonce::DatabaseNonce;
+use self::poll::DatabasePollVote;
+use self::reaction::DatabaseReaction;
+use self::read_state::DatabaseReadState;
+use self::role::DatabaseRole;
+use self::scheduled_message::DatabaseScheduledMessage;
 use self::user::DatabaseUser;
 
 /// This is using old syntax because it doesn't work with new syntax.
@@ -38,11 +63,13 @@ macro_rules! db {
                 let client_options = ClientOptions::parse(std::env::var("DB_URL").expect("No DB_URL found in environment!")).await?;
                 let client = Client::with_options(client_options)?;
                 let db = client.default_database().expect("No database specified in connection string");
-                Ok(Self {
+                let database = Self {
                     $(
                         $i: db.collection(stringify!($i)),
                     )*
-                })
+                };
+                database.create_indexes().await?;
+                Ok(database)
             }
         }
     };
@@ -54,6 +81,27 @@ db! {
     users: DatabaseUser,
     logins: DatabaseLogin,
     guilds: DatabaseGuild,
+    reactions: DatabaseReaction,
+    mentions: DatabaseMention,
+    attachments: DatabaseAttachment,
+    nonces: DatabaseNonce,
+    read_states: DatabaseReadState,
+    scheduled_messages: DatabaseScheduledMessage,
+    poll_votes: DatabasePollVote,
+    emojis: DatabaseEmoji,
+    commands: DatabaseCommand,
+    interactions: DatabaseInteraction,
+    devices: DatabaseDevice,
+    roles: DatabaseRole,
+}
+
+impl Database {
+    async fn create_indexes(&self) -> Result<()> {
+        self.create_message_indexes().await?;
+        self.create_nonce_indexes().await?;
+        self.create_reaction_indexes().await?;
+        self.create_interaction_indexes().await
+    }
 }
 
 pub async fn to_vec<T>(
@@ -73,3 +121,13 @@ where
 
     Ok(out)
 }
+
+/// Whether a write failed because another one inserted the same unique key first.
+pub fn is_duplicate_key(error: &Error) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+    match &*error.kind {
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.code == DUPLICATE_KEY,
+        ErrorKind::Command(error) => error.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
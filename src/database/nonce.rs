@@ -0,0 +1,117 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use mongodb::bson::DateTime;
+use mongodb::error::Result;
+use mongodb::options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument};
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    is_duplicate_key,
+    macros::{eq_keyed, id, keyed},
+    Database,
+};
+
+/// How long a nonce stays reserved for its author.
+pub const NONCE_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// A nonce sent by a user along with a message, kept for `NONCE_WINDOW`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseNonce {
+    pub _id: String,
+    pub message_id: String,
+    pub created: DateTime,
+}
+
+impl DatabaseNonce {
+    fn key(author_id: &str, nonce: &str) -> String {
+        format!("{author_id}:{nonce}")
+    }
+}
+
+fn window_start() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() - NONCE_WINDOW.as_millis() as i64)
+}
+
+impl Database {
+    pub(super) async fn create_nonce_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(keyed!("created", 1))
+            .options(IndexOptions::builder().expire_after(NONCE_WINDOW).build())
+            .build();
+        self.nonces.create_index(index, None).await?;
+        Ok(())
+    }
+
+    /// Fetches the id of the message an author already sent with this nonce.
+    pub async fn fetch_nonce(&self, author_id: &str, nonce: &str) -> Result<Option<String>> {
+        let id = DatabaseNonce::key(author_id, nonce);
+        let created = keyed!("$gte", window_start());
+        Ok(self
+            .nonces
+            .find_one(eq_keyed!("_id", id, created), None)
+            .await?
+            .map(|it| it.message_id))
+    }
+
+    /// Reserves a nonce for a message.
+    ///
+    /// Returns the id of the message that already holds the nonce, if any. When another
+    /// request reserves it at the same time, the upsert of one of them fails with a duplicate
+    /// key, and that one gets the id of the other.
+    pub async fn reserve_nonce(
+        &self,
+        author_id: &str,
+        nonce: &str,
+        message_id: &str,
+    ) -> Result<Option<String>> {
+        let id = DatabaseNonce::key(author_id, nonce);
+
+        // Expired entries may not have been swept by the TTL monitor yet.
+        let created = keyed!("$lt", window_start());
+        self.nonces
+            .delete_one(eq_keyed!("_id", id.clone(), created), None)
+            .await?;
+
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::Before)
+            .build();
+
+        let existing = self
+            .nonces
+            .find_one_and_update(
+                id!(id),
+                keyed!(
+                    "$setOnInsert",
+                    keyed!("message_id", message_id, "created", DateTime::now())
+                ),
+                options,
+            )
+            .await;
+
+        match existing {
+            Ok(existing) => Ok(existing.map(|it| it.message_id)),
+            Err(err) if is_duplicate_key(&err) => self.fetch_nonce(author_id, nonce).await,
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Releases a nonce reserved for a message that could not be sent.
+    pub async fn release_nonce(
+        &self,
+        author_id: &str,
+        nonce: &str,
+        message_id: &str,
+    ) -> Result<()> {
+        let id = DatabaseNonce::key(author_id, nonce);
+        self.nonces
+            .delete_one(eq_keyed!("_id", id, message_id), None)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::bson::{Bson, DateTime};
+use mongodb::error::Result;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument, UpdateOptions};
+use serde::{Deserialize, Serialize};
+
+use crate::structures::message::Message;
+
+use super::{
+    macros::{eq, eq_keyed, id, keyed},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabasePollVote {
+    pub _id: String,
+    pub message_id: String,
+    pub option: u32,
+    pub user_id: String,
+}
+
+impl DatabasePollVote {
+    fn key(message_id: &str, option: u32, user_id: &str) -> String {
+        format!("{message_id}:{option}:{user_id}")
+    }
+
+    /// Users have a single vote on polls that are not multi-select, whatever the option.
+    fn exclusive_key(message_id: &str, user_id: &str) -> String {
+        format!("{message_id}:{user_id}")
+    }
+}
+
+impl Database {
+    /// Adds a vote, replacing the user's other vote on the poll if `exclusive`.
+    ///
+    /// Each vote is a single upsert on a key unique to it, so concurrent votes can't add up.
+    /// A duplicate key error means another vote was counted at the same time.
+    ///
+    /// Returns whether the votes changed.
+    pub async fn create_poll_vote(
+        &self,
+        message_id: &str,
+        option: u32,
+        user_id: &str,
+        exclusive: bool,
+    ) -> Result<bool> {
+        let options = UpdateOptions::builder().upsert(true).build();
+
+        if !exclusive {
+            let id = DatabasePollVote::key(message_id, option, user_id);
+            let result = self
+                .poll_votes
+                .update_one(
+                    id!(id),
+                    keyed!("$setOnInsert", eq!(message_id, option, user_id)),
+                    options,
+                )
+                .await?;
+            return Ok(result.upserted_id.is_some());
+        }
+
+        let id = DatabasePollVote::exclusive_key(message_id, user_id);
+        let result = self
+            .poll_votes
+            .update_one(
+                id!(id.clone()),
+                keyed!("$set", eq!(message_id, option, user_id)),
+                options,
+            )
+            .await?;
+
+        // Votes from before polls kept a single vote per user.
+        let legacy = self
+            .poll_votes
+            .delete_many(
+                eq_keyed!("_id", keyed!("$ne", id), message_id, user_id),
+                None,
+            )
+            .await?;
+
+        Ok(result.upserted_id.is_some() || result.modified_count > 0 || legacy.deleted_count > 0)
+    }
+
+    pub async fn delete_poll_vote(
+        &self,
+        message_id: &str,
+        option: u32,
+        user_id: &str,
+    ) -> Result<bool> {
+        Ok(self
+            .poll_votes
+            .delete_many(eq!(message_id, option, user_id), None)
+            .await?
+            .deleted_count
+            > 0)
+    }
+
+    pub async fn delete_message_poll_votes(&self, message_ids: Vec<String>) -> Result<()> {
+        self.poll_votes
+            .delete_many(keyed!("message_id", keyed!("$in", message_ids)), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Counts the votes for each of the `options` of a poll.
+    pub async fn count_poll_votes(&self, message_id: String, options: usize) -> Result<Vec<u64>> {
+        let votes = to_vec(self.poll_votes.find(eq!(message_id), None).await?).await?;
+
+        let mut counts = vec![0; options];
+        for vote in votes {
+            if let Some(count) = counts.get_mut(vote.option as usize) {
+                *count += 1;
+            }
+        }
+
+        Ok(counts)
+    }
+
+    pub async fn fetch_polls_to_close(&self) -> Result<Vec<Message>> {
+        let filter = keyed!(
+            "poll.results",
+            Bson::Null,
+            "poll.closes_at",
+            keyed!("$lte", DateTime::now())
+        );
+
+        let messages = to_vec(self.messages.find(filter, None).await?).await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    /// Freezes the results of a poll, unless it was already closed.
+    pub async fn close_poll(
+        &self,
+        message_id: String,
+        results: Vec<u64>,
+    ) -> Result<Option<Message>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let results: Vec<i64> = results.into_iter().map(|it| it as i64).collect();
+
+        let message = self
+            .messages
+            .find_one_and_update(
+                keyed!("_id", message_id, "poll.results", Bson::Null),
+                keyed!("$set", keyed!("poll.results", results)),
+                options,
+            )
+            .await?;
+
+        Ok(message.map(Into::into))
+    }
+}
@@ -0,0 +1,139 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use mongodb::bson::DateTime;
+use mongodb::error::Result;
+use mongodb::options::IndexOptions;
+use mongodb::IndexModel;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::reaction::{Reaction, ReactionCount};
+
+use super::{
+    is_duplicate_key,
+    macros::{id, keyed},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseReaction {
+    pub _id: String,
+    pub message_id: String,
+    pub emoji: String,
+    pub user_id: String,
+    /// Missing on reactions from before it was recorded.
+    #[serde(default)]
+    pub created: Option<DateTime>,
+}
+
+/// The number of reactions with an emoji on a message.
+#[derive(Debug, Deserialize)]
+struct ReactionGroup {
+    _id: ReactionGroupKey,
+    count: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReactionGroupKey {
+    message_id: String,
+    emoji: String,
+}
+
+impl DatabaseReaction {
+    fn key(reaction: &Reaction) -> String {
+        format!(
+            "{}:{}:{}",
+            reaction.message_id, reaction.emoji, reaction.user_id
+        )
+    }
+}
+
+impl From<&Reaction> for DatabaseReaction {
+    fn from(value: &Reaction) -> Self {
+        Self {
+            _id: Self::key(value),
+            message_id: value.message_id.clone(),
+            emoji: value.emoji.clone(),
+            user_id: value.user_id.clone(),
+            created: Some(DateTime::now()),
+        }
+    }
+}
+
+impl Database {
+    pub(super) async fn create_reaction_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(keyed!("message_id", 1, "emoji", 1, "user_id", 1))
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.reactions.create_index(index, None).await?;
+        Ok(())
+    }
+
+    /// Returns `false` if the user had already reacted with this emoji, even if at the same time.
+    pub async fn create_reaction(&self, reaction: &Reaction) -> Result<bool> {
+        let reaction = DatabaseReaction::from(reaction);
+        match self.reactions.insert_one(reaction, None).await {
+            Ok(_) => Ok(true),
+            Err(err) if is_duplicate_key(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub async fn delete_reaction(&self, reaction: &Reaction) -> Result<bool> {
+        let id = DatabaseReaction::key(reaction);
+        Ok(self
+            .reactions
+            .delete_one(id!(id), None)
+            .await?
+            .deleted_count
+            > 0)
+    }
+
+    pub async fn delete_message_reactions(&self, message_ids: Vec<String>) -> Result<()> {
+        self.reactions
+            .delete_many(keyed!("message_id", keyed!("$in", message_ids)), None)
+            .await?;
+        Ok(())
+    }
+
+    /// Counts reactions on messages by message id, in the order each emoji was first used.
+    pub async fn fetch_reaction_counts(
+        &self,
+        message_ids: Vec<String>,
+    ) -> Result<HashMap<String, Vec<ReactionCount>>> {
+        let pipeline = vec![
+            keyed!("$match", keyed!("message_id", keyed!("$in", message_ids))),
+            keyed!(
+                "$group",
+                keyed!(
+                    "_id",
+                    keyed!("message_id", "$message_id", "emoji", "$emoji"),
+                    "count",
+                    keyed!("$sum", 1),
+                    "first",
+                    keyed!("$min", "$created")
+                )
+            ),
+            keyed!("$sort", keyed!("first", 1, "_id.emoji", 1)),
+        ];
+        let cursor = self.reactions.aggregate(pipeline, None).await?;
+        let groups: Vec<ReactionGroup> = to_vec(cursor.with_type()).await?;
+
+        let mut counts: HashMap<String, Vec<ReactionCount>> = HashMap::new();
+        for group in groups {
+            counts
+                .entry(group._id.message_id)
+                .or_default()
+                .push(ReactionCount {
+                    emoji: group._id.emoji,
+                    count: group.count,
+                });
+        }
+
+        Ok(counts)
+    }
+}
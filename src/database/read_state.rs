@@ -0,0 +1,111 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::bson::DateTime;
+use mongodb::error::Result;
+use mongodb::options::ReplaceOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::{message::Message, read_state::ReadState};
+
+use super::{
+    is_duplicate_key,
+    macros::{eq, id, keyed},
+    Database,
+};
+
+/// The last message a user has read in a channel.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseReadState {
+    pub _id: String,
+    pub user_id: String,
+    pub channel_id: String,
+    pub last_message_id: String,
+    pub last_read: DateTime,
+}
+
+impl DatabaseReadState {
+    fn key(user_id: &str, channel_id: &str) -> String {
+        format!("{user_id}:{channel_id}")
+    }
+}
+
+impl Database {
+    /// Moves a user's read state in a channel forward to a message.
+    ///
+    /// Returns `false` without changing it if it is already at the message or past it.
+    pub async fn set_read_state(&self, user_id: &str, message: &Message) -> Result<bool> {
+        let state = DatabaseReadState {
+            _id: DatabaseReadState::key(user_id, &message.channel_id),
+            user_id: user_id.to_string(),
+            channel_id: message.channel_id.clone(),
+            last_message_id: message.id.clone(),
+            last_read: DateTime::parse_rfc3339_str(&message.created).unwrap(),
+        };
+
+        // Messages created in the same millisecond are ordered by their ids.
+        let filter = keyed!(
+            "_id",
+            &state._id,
+            "$or",
+            vec![
+                keyed!("last_read", keyed!("$lt", state.last_read)),
+                keyed!(
+                    "last_read",
+                    state.last_read,
+                    "last_message_id",
+                    keyed!("$lt", &state.last_message_id)
+                ),
+            ]
+        );
+
+        // A read state that is not behind is not matched, so the upsert collides with it.
+        let result = self
+            .read_states
+            .replace_one(filter, state, ReplaceOptions::builder().upsert(true).build())
+            .await;
+        match result {
+            Ok(_) => Ok(true),
+            Err(err) if is_duplicate_key(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Fetches a user's read state in a channel, counting the messages and mentions since.
+    pub async fn fetch_read_state(&self, user_id: &str, channel_id: &str) -> Result<ReadState> {
+        let state = self
+            .read_states
+            .find_one(id!(DatabaseReadState::key(user_id, channel_id)), None)
+            .await?;
+
+        let mut messages = keyed!(
+            "channel_id",
+            channel_id,
+            "author_id",
+            keyed!("$ne", user_id)
+        );
+        let mut mentions = keyed!("channel_id", channel_id, "user_id", user_id);
+
+        if let Some(state) = &state {
+            let created = keyed!("$gt", state.last_read);
+            messages.insert("created", created.clone());
+            mentions.insert("created", created);
+        }
+
+        let unread_count = self.messages.count_documents(messages, None).await?;
+        let mention_count = self.mentions.count_documents(mentions, None).await?;
+
+        Ok(ReadState {
+            channel_id: channel_id.to_string(),
+            last_message_id: state.map(|it| it.last_message_id),
+            unread_count,
+            mention_count,
+        })
+    }
+
+    pub async fn delete_channel_read_states(&self, channel_id: &str) -> Result<()> {
+        self.read_states.delete_many(eq!(channel_id), None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,154 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Result;
+use mongodb::options::{FindOneAndUpdateOptions, ReturnDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::structures::role::{GuildMember, Role};
+
+use super::{
+    macros::{basic_create, basic_fetch, eq, id, keyed},
+    to_vec,
+    user::DatabaseUser,
+    Database, DatabaseGuildResponse,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseRole {
+    pub _id: String,
+    pub guild_id: String,
+    pub name: String,
+}
+
+impl From<&Role> for DatabaseRole {
+    fn from(value: &Role) -> Self {
+        Self {
+            _id: value.id.clone(),
+            guild_id: value.guild_id.clone(),
+            name: value.name.clone(),
+        }
+    }
+}
+
+impl From<DatabaseRole> for Role {
+    fn from(value: DatabaseRole) -> Self {
+        Self {
+            id: value._id,
+            guild_id: value.guild_id,
+            name: value.name,
+        }
+    }
+}
+
+impl Database {
+    pub async fn create_role(&self, role: Role) -> Result<Role> {
+        basic_create!(self.roles, DatabaseRole::from, role)
+    }
+
+    pub async fn fetch_role(&self, id: &str) -> Result<Option<Role>> {
+        basic_fetch!(self.roles, id!(id))
+    }
+
+    pub async fn fetch_guild_roles(&self, guild_id: &str) -> Result<Vec<Role>> {
+        let roles = to_vec(self.roles.find(eq!(guild_id), None).await?).await?;
+        Ok(roles.into_iter().map(Into::into).collect())
+    }
+
+    /// Deletes a role, taking it from its members and removing its channel overwrites.
+    pub async fn delete_role(&self, id: &str) -> Result<bool> {
+        self.users
+            .update_many(
+                keyed!("roles", id),
+                keyed!("$pull", keyed!("roles", id)),
+                None,
+            )
+            .await?;
+        self.channels
+            .update_many(
+                keyed!("overwrites.id", id),
+                keyed!("$pull", keyed!("overwrites", keyed!("id", id))),
+                None,
+            )
+            .await?;
+        Ok(self.roles.delete_one(id!(id), None).await?.deleted_count > 0)
+    }
+
+    /// Fetches the members of a guild with their roles in it.
+    pub async fn fetch_guild_members(
+        &self,
+        id: &str,
+    ) -> Result<DatabaseGuildResponse<Vec<GuildMember>>> {
+        if self.fetch_guild(id).await?.is_none() {
+            return Ok(DatabaseGuildResponse::NoGuild);
+        }
+        Ok(DatabaseGuildResponse::Ok(self.fetch_members(id).await?))
+    }
+
+    /// Fetches the members of a guild with their roles, without checking that it exists.
+    pub async fn fetch_members(&self, id: &str) -> Result<Vec<GuildMember>> {
+        let roles = self.fetch_guild_roles(id).await?;
+        let users: Vec<DatabaseUser> =
+            to_vec(self.users.find(keyed!("guilds", id), None).await?).await?;
+
+        Ok(users
+            .into_iter()
+            .map(|it| member(id, it, &roles))
+            .collect())
+    }
+
+    pub async fn fetch_guild_member(&self, id: &str, user: &str) -> Result<Option<GuildMember>> {
+        let Some(user): Option<DatabaseUser> =
+            basic_fetch!(self.users, keyed!("_id", user, "guilds", id))?
+        else {
+            return Ok(None)
+        };
+        let roles = self.fetch_guild_roles(id).await?;
+        Ok(Some(member(id, user, &roles)))
+    }
+
+    /// Gives a role to a member of its guild, or takes it away.
+    pub async fn set_member_role(
+        &self,
+        role: &Role,
+        user: &str,
+        has_role: bool,
+    ) -> Result<Option<GuildMember>> {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .build();
+
+        let operator = if has_role { "$addToSet" } else { "$pull" };
+
+        let user: Option<DatabaseUser> = self
+            .users
+            .find_one_and_update(
+                keyed!("_id", user, "guilds", &role.guild_id),
+                keyed!(operator, keyed!("roles", &role.id)),
+                options,
+            )
+            .await?;
+
+        let Some(user) = user else {
+            return Ok(None)
+        };
+        let roles = self.fetch_guild_roles(&role.guild_id).await?;
+        Ok(Some(member(&role.guild_id, user, &roles)))
+    }
+}
+
+/// Members keep the ids of their roles in every guild, so only those of this guild are kept.
+fn member(guild_id: &str, user: DatabaseUser, roles: &[Role]) -> GuildMember {
+    let member_roles = user
+        .roles
+        .iter()
+        .filter(|it| roles.iter().any(|role| role.id == **it))
+        .cloned()
+        .collect();
+    GuildMember {
+        guild_id: guild_id.to_string(),
+        user: user.into(),
+        roles: member_roles,
+    }
+}
@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::bson::DateTime;
+use mongodb::error::Result;
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::structures::scheduled_message::{RepeatRule, ScheduledMessage};
+
+use super::{
+    macros::{eq, eq_keyed, id, keyed},
+    to_vec, Database,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DatabaseScheduledMessage {
+    pub _id: String,
+    pub channel_id: String,
+    pub author_id: String,
+    pub content: String,
+    pub send_at: DateTime,
+    pub repeat: Option<RepeatRule>,
+}
+
+impl From<&ScheduledMessage> for DatabaseScheduledMessage {
+    fn from(value: &ScheduledMessage) -> Self {
+        Self {
+            _id: value.id.clone(),
+            channel_id: value.channel_id.clone(),
+            author_id: value.author_id.clone(),
+            content: value.content.clone(),
+            send_at: DateTime::parse_rfc3339_str(&value.send_at).unwrap(),
+            repeat: value.repeat,
+        }
+    }
+}
+
+impl From<DatabaseScheduledMessage> for ScheduledMessage {
+    fn from(value: DatabaseScheduledMessage) -> Self {
+        Self {
+            id: value._id,
+            channel_id: value.channel_id,
+            author_id: value.author_id,
+            content: value.content,
+            send_at: value.send_at.try_to_rfc3339_string().unwrap(),
+            repeat: value.repeat,
+        }
+    }
+}
+
+impl Database {
+    pub async fn create_scheduled_message(
+        &self,
+        message: ScheduledMessage,
+    ) -> Result<ScheduledMessage> {
+        self.scheduled_messages
+            .insert_one(DatabaseScheduledMessage::from(&message), None)
+            .await?;
+        Ok(message)
+    }
+
+    pub async fn fetch_scheduled_message(&self, id: String) -> Result<Option<ScheduledMessage>> {
+        Ok(self
+            .scheduled_messages
+            .find_one(id!(id), None)
+            .await?
+            .map(Into::into))
+    }
+
+    /// Fetches a user's scheduled messages, optionally in a single channel, soonest first.
+    pub async fn fetch_user_scheduled_messages(
+        &self,
+        author_id: String,
+        channel_id: Option<String>,
+    ) -> Result<Vec<ScheduledMessage>> {
+        let mut filter = keyed!("author_id", author_id);
+        if let Some(channel_id) = channel_id {
+            filter.insert("channel_id", channel_id);
+        }
+
+        let options = FindOptions::builder().sort(keyed!("send_at", 1)).build();
+
+        let messages = to_vec(self.scheduled_messages.find(filter, options).await?).await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    pub async fn count_user_scheduled_messages(&self, author_id: String) -> Result<u64> {
+        self.scheduled_messages
+            .count_documents(keyed!("author_id", author_id), None)
+            .await
+    }
+
+    pub async fn fetch_due_scheduled_messages(&self) -> Result<Vec<ScheduledMessage>> {
+        let filter = keyed!("send_at", keyed!("$lte", DateTime::now()));
+
+        let messages = to_vec(self.scheduled_messages.find(filter, None).await?).await?;
+
+        Ok(messages.into_iter().map(Into::into).collect())
+    }
+
+    /// Claims a due message so that it is only sent once, rescheduling it to `next` if given.
+    ///
+    /// Returns whether the message was claimed.
+    pub async fn claim_scheduled_message(
+        &self,
+        message: &ScheduledMessage,
+        next: Option<String>,
+    ) -> Result<bool> {
+        let send_at = DateTime::parse_rfc3339_str(&message.send_at).unwrap();
+        let filter = eq_keyed!("_id", message.id.clone(), send_at);
+
+        let Some(next) = next else {
+            return Ok(self
+                .scheduled_messages
+                .delete_one(filter, None)
+                .await?
+                .deleted_count
+                > 0);
+        };
+
+        let next = DateTime::parse_rfc3339_str(next).unwrap();
+
+        Ok(self
+            .scheduled_messages
+            .update_one(filter, keyed!("$set", keyed!("send_at", next)), None)
+            .await?
+            .modified_count
+            > 0)
+    }
+
+    pub async fn delete_scheduled_message(&self, id: String) -> Result<bool> {
+        Ok(self
+            .scheduled_messages
+            .delete_one(id!(id), None)
+            .await?
+            .deleted_count
+            > 0)
+    }
+
+    pub async fn delete_channel_scheduled_messages(&self, channel_id: &str) -> Result<()> {
+        self.scheduled_messages
+            .delete_many(eq!(channel_id), None)
+            .await?;
+        Ok(())
+    }
+}
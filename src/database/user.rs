@@ -22,6 +22,12 @@ pub struct DatabaseUser {
     pub token: String,
     pub guilds: Vec<String>,
     pub gateway_connected: bool,
+    /// The user that created this bot, if it is one.
+    #[serde(default)]
+    pub bot_owner_id: Option<String>,
+    /// The ids of the roles of the user, across all of their guilds.
+    #[serde(default)]
+    pub roles: Vec<String>,
 }
 
 impl From<DatabaseUser> for User {
@@ -30,6 +36,7 @@ impl From<DatabaseUser> for User {
             id: value._id,
             username: value.username,
             discriminator: value.discriminator,
+            bot: value.bot_owner_id.is_some(),
         }
     }
 }
@@ -41,7 +48,11 @@ impl From<&DatabaseUser> for User {
 }
 
 impl Database {
-    async fn create_user_internal(&self, username: &str) -> Result<Option<DatabaseUser>> {
+    async fn create_user_internal(
+        &self,
+        username: &str,
+        bot_owner_id: Option<String>,
+    ) -> Result<Option<DatabaseUser>> {
         let mut discriminator: u32 = rand::thread_rng().gen_range(1..9999);
         let mut count = 1;
 
@@ -72,6 +83,8 @@ impl Database {
             token,
             guilds: vec![],
             gateway_connected: false,
+            bot_owner_id,
+            roles: vec![],
         };
 
         self.users.insert_one(user.clone(), None).await?;
@@ -80,7 +93,20 @@ impl Database {
     }
 
     pub async fn create_user(&self, username: &str) -> Result<Option<(User, String)>> {
-        let Some(user) = self.create_user_internal(username).await? else {
+        let Some(user) = self.create_user_internal(username, None).await? else {
+            return Ok(None)
+        };
+
+        Ok(Some((user.clone().into(), user.token)))
+    }
+
+    pub async fn create_bot(
+        &self,
+        username: &str,
+        owner_id: &str,
+    ) -> Result<Option<(User, String)>> {
+        let owner_id = Some(owner_id.to_string());
+        let Some(user) = self.create_user_internal(username, owner_id).await? else {
             return Ok(None)
         };
 
@@ -0,0 +1,137 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reads channel exports made with DiscordChatExporter in its JSON format, one file per channel.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+
+use super::{
+    read_json, Import, ImportError, ImportedAuthor, ImportedChannel, ImportedMessage, Report,
+};
+
+static CUSTOM_EMOJI: Lazy<Regex> = Lazy::new(|| Regex::new("<a?:([A-Za-z0-9_]+):[0-9]+>").unwrap());
+
+/// Message types that are written by users, rather than by Discord.
+const TYPES: [&str; 2] = ["Default", "Reply"];
+
+#[derive(Deserialize)]
+struct Export {
+    guild: ExportGuild,
+    channel: ExportChannel,
+    messages: Vec<ExportMessage>,
+}
+
+#[derive(Deserialize)]
+struct ExportGuild {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ExportChannel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportMessage {
+    id: String,
+    #[serde(rename = "type")]
+    kind: String,
+    timestamp: String,
+    #[serde(default)]
+    is_pinned: bool,
+    #[serde(default)]
+    content: String,
+    author: ExportUser,
+    #[serde(default)]
+    attachments: Vec<ExportAttachment>,
+    #[serde(default)]
+    mentions: Vec<ExportUser>,
+    reference: Option<ExportReference>,
+}
+
+#[derive(Deserialize)]
+struct ExportUser {
+    id: String,
+    name: String,
+    nickname: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExportAttachment {
+    url: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportReference {
+    message_id: Option<String>,
+}
+
+pub fn read(paths: &[String]) -> Result<Import, ImportError> {
+    let mut guild_name = None;
+    let mut channels = vec![];
+    let mut report = Report::default();
+
+    for path in paths {
+        let export: Export = read_json(path)?;
+        guild_name.get_or_insert(export.guild.name);
+
+        let name = export.channel.name;
+        let mut messages = vec![];
+
+        for message in export.messages {
+            if !TYPES.contains(&message.kind.as_str()) {
+                report.issue(&name, &format!("{} messages skipped", message.kind));
+                continue;
+            }
+            let Ok(created) = DateTime::parse_from_rfc3339(&message.timestamp) else {
+                report.issue(&name, "messages with invalid timestamps skipped");
+                continue;
+            };
+
+            let mut content = content(&message);
+            for attachment in &message.attachments {
+                content.push('\n');
+                content.push_str(&attachment.url);
+                report.issue(&name, "attachments kept as links");
+            }
+
+            messages.push(ImportedMessage {
+                id: message.id,
+                author: ImportedAuthor {
+                    id: message.author.id,
+                    name: message.author.nickname.unwrap_or(message.author.name),
+                },
+                content,
+                created: created.with_timezone(&Utc),
+                reply_to: message.reference.and_then(|it| it.message_id),
+                pinned: message.is_pinned,
+            });
+        }
+
+        channels.push(ImportedChannel { name, messages });
+    }
+
+    Ok(Import {
+        guild_name: guild_name.unwrap_or("Discord".to_string()),
+        channels,
+        report,
+    })
+}
+
+/// Replaces mentions of Discord users with their names, and custom emoji with their shortcodes.
+fn content(message: &ExportMessage) -> String {
+    let mut content = message.content.clone();
+    for user in &message.mentions {
+        let name = format!("@{}", user.nickname.as_ref().unwrap_or(&user.name));
+        content = content
+            .replace(&format!("<@{}>", user.id), &name)
+            .replace(&format!("<@!{}>", user.id), &name);
+    }
+    CUSTOM_EMOJI.replace_all(&content, ":$1:").to_string()
+}
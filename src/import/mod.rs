@@ -0,0 +1,348 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Imports history exported from other chat tools into a new guild.
+//!
+//! ```text
+//! vulpark import discord <export.json>... --owner <user id> [--users <map.json>] [--name <guild>]
+//! vulpark import slack <export directory> --owner <user id> [--users <map.json>] [--name <guild>]
+//! ```
+//!
+//! `--users` is a JSON object mapping user ids of the source to existing user ids.
+//! Authors that are not mapped get placeholder users.
+
+mod discord;
+mod slack;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::Path,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    database,
+    structures::{
+        channel::{Channel, ChannelLocation},
+        guild::Guild,
+        message::Message,
+        user::User,
+        validation::{self, Field},
+    },
+};
+
+/// Messages are inserted this many at a time.
+const BATCH_SIZE: usize = 500;
+
+/// History read from an export, before it is saved.
+pub struct Import {
+    pub guild_name: String,
+    pub channels: Vec<ImportedChannel>,
+    pub report: Report,
+}
+
+pub struct ImportedChannel {
+    pub name: String,
+    pub messages: Vec<ImportedMessage>,
+}
+
+pub struct ImportedMessage {
+    /// The id of the message in the source, to link replies.
+    pub id: String,
+    pub author: ImportedAuthor,
+    pub content: String,
+    pub created: DateTime<Utc>,
+    pub reply_to: Option<String>,
+    pub pinned: bool,
+}
+
+pub struct ImportedAuthor {
+    pub id: String,
+    pub name: String,
+}
+
+/// What was imported, and what could not be.
+#[derive(Default)]
+pub struct Report {
+    pub guild_id: Option<String>,
+    pub channels: usize,
+    pub messages: usize,
+    pub mapped_users: usize,
+    pub placeholder_users: usize,
+    /// Counts of things that were skipped or changed, by description.
+    pub issues: BTreeMap<String, usize>,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    Usage(String),
+    Io { path: String, message: String },
+    Json { path: String, message: String },
+    UnknownUser(String),
+    Database(mongodb::error::Error),
+}
+
+pub async fn run(args: &[String]) {
+    match import(args).await {
+        Ok(report) => println!("{report}"),
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn import(args: &[String]) -> Result<Report, ImportError> {
+    let mut paths = vec![];
+    let mut owner = None;
+    let mut users = None;
+    let mut name = None;
+
+    let mut args = args.iter();
+    let source = args.next().ok_or(ImportError::usage())?;
+    while let Some(arg) = args.next() {
+        let value = match arg.as_str() {
+            "--owner" => &mut owner,
+            "--users" => &mut users,
+            "--name" => &mut name,
+            _ => {
+                paths.push(arg.clone());
+                continue;
+            }
+        };
+        *value = Some(args.next().ok_or(ImportError::usage())?.clone());
+    }
+
+    let owner = owner.ok_or(ImportError::usage())?;
+    if paths.is_empty() {
+        return Err(ImportError::usage());
+    }
+
+    let mut import = match source.as_str() {
+        "discord" => discord::read(&paths)?,
+        "slack" => match paths.as_slice() {
+            [path] => slack::read(path)?,
+            _ => return Err(ImportError::usage()),
+        },
+        _ => return Err(ImportError::usage()),
+    };
+
+    if let Some(name) = name {
+        import.guild_name = name;
+    }
+
+    let users = match users {
+        Some(path) => read_json(&path)?,
+        None => HashMap::new(),
+    };
+
+    import.save(&owner, users).await
+}
+
+impl Import {
+    /// Creates the guild, its channels and their messages.
+    pub async fn save(
+        mut self,
+        owner_id: &str,
+        users: HashMap<String, String>,
+    ) -> Result<Report, ImportError> {
+        let database = database().await;
+
+        if database.fetch_user(owner_id).await?.is_none() {
+            return Err(ImportError::UnknownUser(owner_id.to_string()));
+        }
+        for id in users.values() {
+            if database.fetch_user(id).await?.is_none() {
+                return Err(ImportError::UnknownUser(id.clone()));
+            }
+        }
+
+        let guild = Guild::new(&self.guild_name, owner_id).insert().await?;
+        self.report.guild_id = Some(guild.id.clone());
+
+        // Placeholder users don't join the guild, as nobody can log in as them.
+        for id in users.values() {
+            database.join_guild(&guild.id, id).await?;
+        }
+
+        let mut authors = Authors {
+            users,
+            resolved: HashMap::new(),
+        };
+
+        for channel in std::mem::take(&mut self.channels) {
+            self.save_channel(&guild, channel, &mut authors).await?;
+        }
+
+        Ok(self.report)
+    }
+
+    async fn save_channel(
+        &mut self,
+        guild: &Guild,
+        mut imported: ImportedChannel,
+        authors: &mut Authors,
+    ) -> Result<(), ImportError> {
+        let location = ChannelLocation::Guild {
+            guild: guild.id.clone(),
+        };
+        let channel = Channel::new(&imported.name, location).insert().await?;
+        self.report.channels += 1;
+
+        imported.messages.sort_by_key(|it| it.created);
+
+        let max = Field::Content.max_length();
+        let mut ids: HashMap<String, String> = HashMap::new();
+        let mut batch = vec![];
+
+        for imported in imported.messages {
+            let mut content = imported.content;
+            if content.trim().is_empty() {
+                self.report.issue(&channel.name, "empty messages skipped");
+                continue;
+            }
+            if validation::exceeds(&content, max) {
+                content = validation::truncate(&content, max);
+                self.report.issue(&channel.name, "messages truncated");
+            }
+
+            let author_id = authors.resolve(&imported.author, &mut self.report).await?;
+
+            let mut message = Message::new(channel.id.clone(), String::new(), content)
+                .with_created(imported.created)
+                .with_reply_to(imported.reply_to.and_then(|it| ids.get(&it).cloned()));
+            message.author_id = author_id;
+            if imported.pinned {
+                message.pinned_at = Some(message.created.clone());
+            }
+
+            ids.insert(imported.id, message.id.clone());
+            batch.push(message);
+
+            if batch.len() >= BATCH_SIZE {
+                self.save_batch(&mut batch).await?;
+            }
+        }
+
+        self.save_batch(&mut batch).await
+    }
+
+    async fn save_batch(&mut self, batch: &mut Vec<Message>) -> Result<(), ImportError> {
+        database().await.create_messages(batch).await?;
+        self.report.messages += batch.len();
+        batch.clear();
+        Ok(())
+    }
+}
+
+/// Resolves the authors of imported messages to users, creating placeholders as needed.
+struct Authors {
+    users: HashMap<String, String>,
+    resolved: HashMap<String, Option<String>>,
+}
+
+impl Authors {
+    async fn resolve(
+        &mut self,
+        author: &ImportedAuthor,
+        report: &mut Report,
+    ) -> Result<Option<String>, ImportError> {
+        if let Some(id) = self.resolved.get(&author.id) {
+            return Ok(id.clone());
+        }
+
+        let id = if let Some(id) = self.users.get(&author.id) {
+            report.mapped_users += 1;
+            Some(id.clone())
+        } else if let Some((user, _)) = User::create(&author.name).await? {
+            report.placeholder_users += 1;
+            Some(user.id)
+        } else {
+            report.issue(&author.name, "authors that could not be created");
+            None
+        };
+
+        self.resolved.insert(author.id.clone(), id.clone());
+        Ok(id)
+    }
+}
+
+impl Report {
+    pub fn issue(&mut self, subject: &str, description: &str) {
+        *self
+            .issues
+            .entry(format!("{subject}: {description}"))
+            .or_default() += 1;
+    }
+}
+
+impl ImportError {
+    fn usage() -> Self {
+        Self::Usage(
+            "Usage: vulpark import <discord|slack> <paths>... --owner <user id> [--users <map.json>] [--name <guild name>]"
+                .to_string(),
+        )
+    }
+
+    fn io(path: &Path, err: impl Display) -> Self {
+        Self::Io {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    fn json(path: &Path, err: impl Display) -> Self {
+        Self::Json {
+            path: path.display().to_string(),
+            message: err.to_string(),
+        }
+    }
+}
+
+/// Reads and parses a JSON file.
+fn read_json<T: serde::de::DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, ImportError> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|err| ImportError::io(path, err))?;
+    serde_json::from_slice(&data).map_err(|err| ImportError::json(path, err))
+}
+
+impl From<mongodb::error::Error> for ImportError {
+    fn from(value: mongodb::error::Error) -> Self {
+        Self::Database(value)
+    }
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Usage(usage) => write!(f, "{usage}"),
+            Self::Io { path, message } => write!(f, "Could not read {path}: {message}"),
+            Self::Json { path, message } => write!(f, "Could not parse {path}: {message}"),
+            Self::UnknownUser(id) => write!(f, "No user with the id {id}"),
+            Self::Database(err) => write!(f, "Database error: {err}"),
+        }
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(id) = &self.guild_id {
+            writeln!(f, "Imported into guild {id}")?;
+        }
+        writeln!(f, "Channels: {}", self.channels)?;
+        writeln!(f, "Messages: {}", self.messages)?;
+        writeln!(f, "Mapped users: {}", self.mapped_users)?;
+        writeln!(f, "Placeholder users: {}", self.placeholder_users)?;
+        if self.issues.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "\nNot imported as is:")?;
+        for (issue, count) in &self.issues {
+            writeln!(f, "  {count} x {issue}")?;
+        }
+        Ok(())
+    }
+}
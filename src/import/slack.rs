@@ -0,0 +1,282 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reads Slack workspace exports, once the archive has been extracted to a directory.
+
+use std::{collections::HashMap, path::Path};
+
+use chrono::{DateTime, TimeZone, Utc};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+
+use super::{
+    read_json, Import, ImportError, ImportedAuthor, ImportedChannel, ImportedMessage, Report,
+};
+
+static SPECIAL: Lazy<Regex> = Lazy::new(|| Regex::new(r"<([^<>|]+)(?:\|([^<>]*))?>").unwrap());
+
+/// Message subtypes that are written by users, rather than by Slack.
+const SUBTYPES: [&str; 4] = [
+    "bot_message",
+    "file_share",
+    "me_message",
+    "thread_broadcast",
+];
+
+/// Lists of conversations in an export, with their messages in directories named after them.
+const CONVERSATIONS: [&str; 2] = ["channels.json", "groups.json"];
+
+#[derive(Deserialize)]
+struct ExportConversation {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ExportUser {
+    id: String,
+    name: String,
+    #[serde(default)]
+    profile: ExportProfile,
+}
+
+#[derive(Default, Deserialize)]
+struct ExportProfile {
+    #[serde(default)]
+    display_name: String,
+    #[serde(default)]
+    real_name: String,
+}
+
+#[derive(Deserialize)]
+struct ExportMessage {
+    subtype: Option<String>,
+    user: Option<String>,
+    bot_id: Option<String>,
+    username: Option<String>,
+    #[serde(default)]
+    text: String,
+    ts: String,
+    thread_ts: Option<String>,
+    #[serde(default)]
+    files: Vec<ExportFile>,
+    #[serde(default)]
+    pinned_to: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ExportFile {
+    name: Option<String>,
+    url_private: Option<String>,
+}
+
+pub fn read(path: &str) -> Result<Import, ImportError> {
+    let root = Path::new(path);
+    if root.is_file() {
+        return Err(ImportError::Usage(
+            "Extract the Slack export archive and import the directory".to_string(),
+        ));
+    }
+
+    let users: Vec<ExportUser> = read_json(root.join("users.json"))?;
+    let names: HashMap<String, String> = users
+        .into_iter()
+        .map(|it| {
+            let name = [it.profile.display_name, it.profile.real_name]
+                .into_iter()
+                .find(|it| !it.is_empty())
+                .unwrap_or(it.name);
+            (it.id, name)
+        })
+        .collect();
+
+    let mut channels = vec![];
+    let mut report = Report::default();
+
+    for list in CONVERSATIONS {
+        if !root.join(list).exists() {
+            continue;
+        }
+        let conversations: Vec<ExportConversation> = read_json(root.join(list))?;
+        for conversation in conversations {
+            let messages = read_channel(
+                &root.join(&conversation.name),
+                &conversation.name,
+                &names,
+                &mut report,
+            )?;
+            channels.push(ImportedChannel {
+                name: conversation.name,
+                messages,
+            });
+        }
+    }
+
+    let guild_name = root
+        .file_name()
+        .map_or("Slack".to_string(), |it| it.to_string_lossy().to_string());
+
+    Ok(Import {
+        guild_name,
+        channels,
+        report,
+    })
+}
+
+/// Reads the daily message files of a channel.
+fn read_channel(
+    dir: &Path,
+    name: &str,
+    names: &HashMap<String, String>,
+    report: &mut Report,
+) -> Result<Vec<ImportedMessage>, ImportError> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        report.issue(name, "channels without a message directory");
+        return Ok(vec![]);
+    };
+
+    let mut days: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|it| it.path())
+        .filter(|it| it.extension().is_some_and(|it| it == "json"))
+        .collect();
+    days.sort();
+
+    let mut messages = vec![];
+    for day in days {
+        let exported: Vec<ExportMessage> = read_json(&day)?;
+        for message in exported {
+            if let Some(subtype) = &message.subtype {
+                if !SUBTYPES.contains(&subtype.as_str()) {
+                    report.issue(name, &format!("{subtype} messages skipped"));
+                    continue;
+                }
+            }
+            let Some(created) = timestamp(&message.ts) else {
+                report.issue(name, "messages with invalid timestamps skipped");
+                continue;
+            };
+
+            let author_id = message
+                .user
+                .clone()
+                .or(message.bot_id.clone())
+                .unwrap_or_default();
+            let author_name = names
+                .get(&author_id)
+                .cloned()
+                .or(message.username.clone())
+                .unwrap_or("Unknown".to_string());
+
+            let mut content = text(&message.text, names);
+            for file in &message.files {
+                content.push('\n');
+                match (&file.url_private, &file.name) {
+                    (Some(url), _) => content.push_str(url),
+                    (None, Some(name)) => content.push_str(name),
+                    (None, None) => {}
+                }
+                report.issue(name, "files kept as links");
+            }
+
+            let reply_to = message.thread_ts.filter(|it| *it != message.ts);
+
+            messages.push(ImportedMessage {
+                id: message.ts,
+                author: ImportedAuthor {
+                    id: author_id,
+                    name: author_name,
+                },
+                content,
+                created,
+                reply_to,
+                pinned: !message.pinned_to.is_empty(),
+            });
+        }
+    }
+
+    Ok(messages)
+}
+
+/// Parses a Slack timestamp, which is seconds since the epoch with microseconds.
+fn timestamp(ts: &str) -> Option<DateTime<Utc>> {
+    let (seconds, micros) = ts.split_once('.').unwrap_or((ts, "0"));
+    let nanos = format!("{micros:0<9}").get(..9)?.parse().ok()?;
+    Utc.timestamp_opt(seconds.parse().ok()?, nanos).single()
+}
+
+/// Converts Slack's mrkdwn escapes, user and channel references and links to plain markdown.
+fn text(text: &str, names: &HashMap<String, String>) -> String {
+    let text = SPECIAL.replace_all(text, |captures: &Captures| {
+        let target = &captures[1];
+        let label = captures.get(2).map(|it| it.as_str());
+        if let Some(id) = target.strip_prefix('@') {
+            let name = names.get(id).map_or(label.unwrap_or(id), String::as_str);
+            return format!("@{name}");
+        }
+        if let Some(id) = target.strip_prefix('#') {
+            return format!("#{}", label.unwrap_or(id));
+        }
+        if let Some(command) = target.strip_prefix('!') {
+            return match command {
+                "channel" | "here" | "everyone" => "@everyone".to_string(),
+                _ => label.unwrap_or(command).to_string(),
+            };
+        }
+        match label {
+            Some(label) => format!("[{label}]({target})"),
+            None => target.to_string(),
+        }
+    });
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_keep_microseconds() {
+        let parsed = timestamp("1700000000.123456").unwrap();
+        assert_eq!(parsed.timestamp(), 1_700_000_000);
+        assert_eq!(parsed.timestamp_subsec_micros(), 123_456);
+
+        assert_eq!(timestamp("1700000000").unwrap().timestamp(), 1_700_000_000);
+        assert_eq!(
+            timestamp("1700000000.5").unwrap().timestamp_subsec_millis(),
+            500
+        );
+    }
+
+    #[test]
+    fn invalid_timestamps_are_rejected() {
+        assert_eq!(timestamp(""), None);
+        assert_eq!(timestamp("yesterday"), None);
+        assert_eq!(timestamp("1700000000.abc"), None);
+    }
+
+    #[test]
+    fn references_become_names() {
+        let names = HashMap::from([("U1".to_string(), "Ada".to_string())]);
+        assert_eq!(text("hi <@U1>", &names), "hi @Ada");
+        assert_eq!(text("hi <@U2|bob>", &names), "hi @bob");
+        assert_eq!(text("see <#C1|general>", &names), "see #general");
+        assert_eq!(
+            text("<!here> and <!channel>", &names),
+            "@everyone and @everyone"
+        );
+    }
+
+    #[test]
+    fn links_and_escapes_become_markdown() {
+        let names = HashMap::new();
+        assert_eq!(
+            text("<https://example.com|docs> <https://example.com>", &names),
+            "[docs](https://example.com) https://example.com"
+        );
+        assert_eq!(text("a &lt;b&gt; &amp;lt;", &names), "a <b> &lt;");
+    }
+}
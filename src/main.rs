@@ -17,25 +17,31 @@ use database::Database;
 use dotenv::dotenv;
 use rand::Rng;
 use std::sync::OnceLock;
+use storage::BlobStorage;
 use ulid::Ulid;
 
 mod database;
+mod import;
 mod route;
+mod storage;
 mod structures;
-
-macro map_async($v: expr, $out: expr, $f: expr) {
-    for i in $v.into_iter() {
-        $out.push($f.call((i,)).await);
-    }
-}
+mod task;
 
 #[tokio::main]
 async fn main() {
     dotenv().ok();
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("import") {
+        import::run(&args[1..]).await;
+        return;
+    }
+
     route::init().await;
 }
 
 static DATABASE: OnceLock<Database> = OnceLock::new();
+static STORAGE: OnceLock<Box<dyn BlobStorage>> = OnceLock::new();
 
 #[must_use]
 pub fn generate_ulid() -> String {
@@ -59,6 +65,13 @@ pub async fn database() -> &'static Database {
     DATABASE.get().unwrap()
 }
 
+/// # Panics
+/// - if the configured storage backend cannot be created
+#[must_use]
+pub fn storage() -> &'static dyn BlobStorage {
+    STORAGE.get_or_init(storage::create).as_ref()
+}
+
 pub macro with_lock($mutex: expr) {
     $mutex.lock().await
 }
@@ -0,0 +1,219 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use futures::{StreamExt, TryStreamExt};
+use rweb::*;
+use warp::{
+    http::header::{
+        CONTENT_DISPOSITION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS,
+    },
+    hyper::{body::Buf, StatusCode},
+    multipart::FormData,
+    Filter, Rejection, Reply,
+};
+
+use crate::{
+    database, storage,
+    structures::{
+        attachment::{Attachment, AttachmentFile},
+        error::ResponseResult,
+    },
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    HttpError,
+};
+
+pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    upload().or(download())
+}
+
+pub(super) fn upload_form() -> impl Filter<Extract = (FormData,), Error = Rejection> + Clone {
+    warp::multipart::form().max_length(Attachment::max_size())
+}
+
+/// Reads the `file` part of an upload, returning its name, content type and contents.
+pub(super) async fn read_file(form: FormData) -> Result<Option<(String, String, Vec<u8>)>, warp::Error> {
+    let mut parts = form.into_stream();
+
+    while let Some(part) = parts.next().await {
+        let part = part?;
+        if part.name() != "file" {
+            continue;
+        }
+
+        let filename = part.filename().unwrap_or_default().to_string();
+        let content_type = part
+            .content_type()
+            .unwrap_or("application/octet-stream")
+            .to_string();
+
+        let mut data = vec![];
+        let mut stream = part.stream();
+        while let Some(chunk) = stream.try_next().await? {
+            data.extend_from_slice(chunk.chunk());
+        }
+
+        return Ok(Some((filename, content_type, data)));
+    }
+
+    Ok(None)
+}
+
+#[post("/attachments")]
+pub async fn upload(
+    #[header = "Authentication"] token: String,
+    #[filter = "upload_form"] form: FormData,
+) -> ResponseResult<Attachment> {
+    let user = with_login!(token);
+
+    let file = match read_file(form).await {
+        Ok(file) => file,
+        Err(err) => return err!(HttpError::Other(err.to_string()), StatusCode::BAD_REQUEST),
+    };
+
+    let Some((filename, content_type, data)) = file else {
+        return err!(HttpError::MissingAttachment, StatusCode::BAD_REQUEST)
+    };
+
+    let attachment = Attachment::new(&filename, &content_type, data.len() as u64, &user.id);
+
+    if let Err(err) = storage().put(&attachment.id, data, &content_type).await {
+        return err!(HttpError::Storage(err), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let id = attachment.id.clone();
+    let inserted = attachment.insert().await;
+    if inserted.is_err() {
+        let _ = storage().delete(&id).await;
+    }
+
+    ok!(unwrap!(inserted))
+}
+
+#[get("/attachments/{id}")]
+pub async fn download(
+    #[header = "Authentication"] token: String,
+    id: String,
+) -> Result<impl Reply, Rejection> {
+    let file = match fetch_file(token, id).await?.success() {
+        Ok(file) => file,
+        Err(err) => return Ok(err.into_response()),
+    };
+
+    let disposition = if file.attachment.is_inline() {
+        "inline"
+    } else {
+        "attachment"
+    };
+
+    let mut response = warp::reply::Response::new(file.data.into());
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, header_value(&file.attachment.content_type));
+    headers.insert(X_CONTENT_TYPE_OPTIONS, header_value("nosniff"));
+    headers.insert(CONTENT_SECURITY_POLICY, header_value("sandbox"));
+    headers.insert(
+        CONTENT_DISPOSITION,
+        content_disposition(disposition, &file.attachment.filename),
+    );
+
+    Ok(response)
+}
+
+/// A Content-Disposition header naming the file, as in RFC 6266.
+///
+/// Clients that understand RFC 5987 read the exact name from `filename*`, others get a copy
+/// with anything that is not printable ASCII replaced.
+pub(super) fn content_disposition(disposition: &str, filename: &str) -> warp::http::HeaderValue {
+    let fallback: String = filename
+        .chars()
+        .map(|it| match it {
+            ' '..='~' if it != '"' && it != '\\' => it,
+            _ => '_',
+        })
+        .collect();
+
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' => encoded.push(byte as char),
+            b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+
+    let value = format!("{disposition}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}");
+    warp::http::HeaderValue::from_str(&value)
+        .unwrap_or(warp::http::HeaderValue::from_static("attachment"))
+}
+
+/// A header value, falling back to the generic content type if it is not valid.
+pub(super) fn header_value(value: &str) -> warp::http::HeaderValue {
+    warp::http::HeaderValue::from_str(value)
+        .unwrap_or(warp::http::HeaderValue::from_static("application/octet-stream"))
+}
+
+/// Fetches an attachment, checking that the user can see the channel it was sent in.
+///
+/// Attachments that have not been sent yet are only visible to their uploader.
+async fn fetch_file(token: String, id: String) -> ResponseResult<AttachmentFile> {
+    let user = with_login!(token);
+
+    let Some((attachment, channel_id)) = unwrap!(database().await.fetch_attachment(&id).await) else {
+        return not_found!("Attachment")
+    };
+
+    match channel_id {
+        Some(channel_id) => {
+            let Some(channel) = unwrap!(database().await.fetch_channel(channel_id.clone()).await) else {
+                return not_found!("Channel")
+            };
+
+            let users = channel.get_users().await.unwrap_or(vec![]);
+
+            if !users.contains(&user.id) {
+                return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+            }
+        }
+        None => {
+            if attachment.uploader_id != user.id {
+                return not_found!("Attachment");
+            }
+        }
+    }
+
+    let data = match storage().get(&attachment.id).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return not_found!("Attachment"),
+        Err(err) => return err!(HttpError::Storage(err), StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    ok!(AttachmentFile { attachment, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_disposition_keeps_ascii_names() {
+        let value = content_disposition("attachment", "report 2.pdf");
+        assert_eq!(
+            value,
+            "attachment; filename=\"report 2.pdf\"; filename*=UTF-8''report%202.pdf"
+        );
+    }
+
+    #[test]
+    fn content_disposition_encodes_other_names() {
+        let value = content_disposition("inline", "café \"menu\".png");
+        assert_eq!(
+            value,
+            "inline; filename=\"caf_ _menu_.png\"; filename*=UTF-8''caf%C3%A9%20%22menu%22.png"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        error::ResponseResult,
+        event::Event,
+        guild::GuildResponse,
+        user::{BotCreate, User, UserLoginResponse},
+        validation::{self, Field},
+    },
+    with_lock,
+};
+
+use super::{
+    macros::{err, expect, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
+};
+
+pub fn routes(
+    clients: &ClientHolder,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let create = create();
+
+    let add = add(clients.clone());
+
+    create.or(add).map(Reply::into_response).boxed()
+}
+
+/// Creates a bot owned by the user, returning its token.
+#[post("/bots")]
+pub async fn create(
+    #[header = "Authentication"] token: String,
+    #[json] create: BotCreate,
+) -> ResponseResult<UserLoginResponse> {
+    let user = with_login!(token);
+
+    if user.bot {
+        return err!(HttpError::BotsCannotCreateBots, StatusCode::FORBIDDEN);
+    }
+
+    if let Err(err) = validation::name(Field::Username, &create.username) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    let bot = expect!(
+        unwrap!(User::create_bot(&create.username, &user.id).await),
+        StatusCode::INTERNAL_SERVER_ERROR,
+        HttpError::TooManyUsers
+    );
+
+    ok!(bot.into())
+}
+
+/// Adds a bot to a guild. Only the owner of the guild may add bots.
+#[put("/guilds/{id}/bots/{bot_id}")]
+pub async fn add(
+    id: String,
+    bot_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<User> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let Some(bot) = unwrap!(database().await.fetch_user(&bot_id).await) else {
+        return not_found!("Bot")
+    };
+
+    if !bot.bot {
+        return err!(HttpError::NotABot, StatusCode::BAD_REQUEST);
+    }
+
+    unwrap!(database().await.join_guild(&guild.id, &bot.id).await);
+
+    let resp = GuildResponse::from(guild).await;
+    let resp = unwrap!(resp);
+
+    with_lock!(clients).dispatch_users(vec![bot.id.clone()], &Event::GuildCreate(resp));
+
+    ok!(bot)
+}
@@ -3,21 +3,35 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use rweb::*;
-use warp::{Filter, Rejection, Reply};
+use warp::{
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    hyper::{Body, StatusCode},
+    Filter, Rejection, Reply,
+};
 
 use crate::{
     database,
     structures::{
-        channel::{Channel, ChannelCreate, ChannelResponse},
+        channel::{
+            Channel, ChannelCreate, ChannelKind, ChannelLocation, ChannelPinsResponse,
+            ChannelPosition, ChannelResponse, ChannelUpdate, GuildChannelsResponse, ThreadCreate,
+            AUTO_ARCHIVE_DURATIONS,
+        },
+        encryption::EncryptionError,
         error::ResponseResult,
         event::Event,
+        message::MessageResponse,
+        role::{GuildPermissions, OverwriteKind, OverwriteUpdate, Permission, PermissionOverwrite},
+        transcript::{Transcript, TranscriptQuery},
+        validation::{self, Field},
     },
     with_lock,
 };
 
 use super::{
-    macros::{not_found, ok, unwrap, with_login},
-    ClientHolder,
+    attachment::{content_disposition, header_value},
+    macros::{err, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
 };
 
 pub fn routes(
@@ -27,19 +41,92 @@ pub fn routes(
 
     let fetch = fetch();
 
-    create.or(fetch)
+    let manage = update(clients.clone()).or(delete(clients.clone()));
+
+    let guild_channels = fetch_guild_channels().or(reorder(clients.clone()));
+
+    let overwrites = set_overwrite(clients.clone()).or(delete_overwrite(clients.clone()));
+
+    let pins = fetch_pins()
+        .or(pin(clients.clone()))
+        .or(unpin(clients.clone()));
+
+    let threads = create_thread(clients.clone()).or(fetch_threads());
+
+    let transcript = transcript();
+
+    create
+        .or(fetch)
+        .or(manage)
+        .or(guild_channels)
+        .or(overwrites)
+        .or(pins)
+        .or(threads)
+        .or(transcript)
+        .map(Reply::into_response)
+        .boxed()
 }
 
+const MAX_PINS: u64 = 50;
+const DEFAULT_AUTO_ARCHIVE: u64 = 24 * 60;
+
+/// Creates a channel. Guild channels may only be created by the guild owner.
 #[post("/channels")]
 pub async fn create(
     #[header = "Authentication"] token: String,
     #[json] create: ChannelCreate,
     #[data] clients: ClientHolder,
 ) -> ResponseResult<ChannelResponse> {
-    with_login!(token);
+    let user = with_login!(token);
+
+    if let Err(err) = validation::name(Field::ChannelName, &create.name) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let ChannelLocation::Thread { .. } = create.location {
+        return err!(HttpError::InvalidThread, StatusCode::BAD_REQUEST);
+    }
+
+    if create.encrypted && !matches!(create.location, ChannelLocation::Dm { .. }) {
+        return err!(
+            HttpError::Encryption(EncryptionError::OnlyDms),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    let guild = match &create.location {
+        ChannelLocation::Guild { guild } => Some(guild),
+        _ => None,
+    };
+
+    if create.kind != ChannelKind::Text && guild.is_none() {
+        return err!(HttpError::InvalidChannelKind, StatusCode::BAD_REQUEST);
+    }
+
+    if let Some(guild) = guild {
+        let Some(guild) = unwrap!(database().await.fetch_guild(guild).await) else {
+            return not_found!("Guild")
+        };
+        if guild.owner_id != user.id {
+            return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+        }
+    }
+
+    if let Some(category) = &create.category {
+        let category = unwrap!(database().await.fetch_channel(category.clone()).await);
+        let valid = category.is_some_and(|it| {
+            it.kind == ChannelKind::Category
+                && matches!(&it.location, ChannelLocation::Guild { guild: it } if Some(it) == guild)
+        });
+        if !valid || create.kind == ChannelKind::Category {
+            return err!(HttpError::InvalidCategory, StatusCode::BAD_REQUEST);
+        }
+    }
 
     let channel = unwrap!(
         Channel::new(&create.name, create.location.clone())
+            .with_encryption(create.encrypted)
+            .with_kind(create.kind, create.category.clone())
             .insert()
             .await
     );
@@ -59,11 +146,540 @@ pub async fn fetch(
     #[header = "Authentication"]  token: String,
     id: String
 ) -> ResponseResult<ChannelResponse> {
-    with_login!(token);
+    let user = with_login!(token);
 
     let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
         return not_found!("Channel")
     };
 
+    if !channel.can_view(&user.id).await {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
     ok!(ChannelResponse::from_channel(channel))
 }
+
+/// Renames a channel or changes its topic or position.
+///
+/// Guild channels and threads may only be changed by the guild owner, DMs by their members.
+#[patch("/channels/{id}")]
+pub async fn update(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[json] update: ChannelUpdate,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelResponse> {
+    let user = with_login!(token);
+
+    if let Some(name) = &update.name {
+        if let Err(err) = validation::name(Field::ChannelName, name) {
+            return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    if let Some(topic) = &update.topic {
+        if let Err(err) = validation::text(Field::ChannelTopic, topic) {
+            return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !can_manage(&channel, &users, &user.id).await {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let updated = database().await.update_channel(channel.id, &update).await;
+
+    let Some(channel) = unwrap!(updated) else {
+        return not_found!("Channel")
+    };
+
+    let resp = ChannelResponse::from_channel(channel);
+
+    with_lock!(clients).dispatch_users(users, &Event::ChannelUpdate(resp.clone()));
+
+    ok!(resp)
+}
+
+/// Deletes a channel with its threads and messages.
+///
+/// Guild channels and threads may only be deleted by the guild owner, DMs by their members.
+#[delete("/channels/{id}")]
+pub async fn delete(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelResponse> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !can_manage(&channel, &users, &user.id).await {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let deleted = unwrap!(channel.clone().delete().await);
+
+    let lock = with_lock!(clients);
+    for channel in deleted {
+        let resp = ChannelResponse::from_channel(channel);
+        lock.dispatch_users(users.clone(), &Event::ChannelDelete(resp));
+    }
+
+    ok!(ChannelResponse::from_channel(channel))
+}
+
+/// Fetches the channels of a guild as an ordered tree of categories and their channels.
+#[get("/guilds/{id}/channels")]
+pub async fn fetch_guild_channels(
+    id: String,
+    #[header = "Authentication"] token: String,
+) -> ResponseResult<GuildChannelsResponse> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    let permissions = unwrap!(GuildPermissions::fetch_member(&guild.id, &user.id).await);
+    let Some(permissions) = permissions.filter(|it| it.is_member(&user.id)) else {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN)
+    };
+
+    let mut channels = unwrap!(database().await.fetch_guild_channels(&guild.id).await);
+
+    channels.retain(|it| {
+        permissions
+            .resolve(&it.overwrites, &user.id)
+            .contains(&Permission::ViewChannel)
+    });
+
+    ok!(GuildChannelsResponse::new(guild.id, channels))
+}
+
+/// Moves guild channels in bulk, setting their position and category. Only for the guild owner.
+#[patch("/guilds/{id}/channels")]
+pub async fn reorder(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[json] positions: Vec<ChannelPosition>,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<GuildChannelsResponse> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let channels = unwrap!(database().await.fetch_guild_channels(&guild.id).await);
+
+    for (i, position) in positions.iter().enumerate() {
+        if positions[..i].iter().any(|it| it.id == position.id) {
+            return err!(HttpError::InvalidPositions, StatusCode::BAD_REQUEST);
+        }
+
+        let Some(channel) = channels.iter().find(|it| it.id == position.id) else {
+            return not_found!("Channel")
+        };
+
+        if let Some(category) = &position.category {
+            let valid = channel.kind != ChannelKind::Category
+                && channels
+                    .iter()
+                    .any(|it| it.id == *category && it.kind == ChannelKind::Category);
+            if !valid {
+                return err!(HttpError::InvalidCategory, StatusCode::BAD_REQUEST);
+            }
+        }
+    }
+
+    let mut moved = vec![];
+    for position in positions {
+        let updated = database()
+            .await
+            .set_channel_position(&position.id, position.position, position.category)
+            .await;
+        moved.extend(unwrap!(updated));
+    }
+
+    let channels = unwrap!(database().await.fetch_guild_channels(&guild.id).await);
+
+    for channel in moved {
+        let users = channel.get_users().await.unwrap_or(vec![]);
+        let resp = ChannelResponse::from_channel(channel);
+        with_lock!(clients).dispatch_users(users, &Event::ChannelUpdate(resp));
+    }
+
+    ok!(GuildChannelsResponse::new(guild.id, channels))
+}
+
+/// Sets the overwrite of a role or member in a guild channel. Only for the guild owner.
+///
+/// The guild id targets the everyone role.
+#[put("/channels/{id}/overwrites/{target_id}")]
+pub async fn set_overwrite(
+    id: String,
+    target_id: String,
+    #[header = "Authentication"] token: String,
+    #[json] update: OverwriteUpdate,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelResponse> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let ChannelLocation::Guild { guild } = &channel.location else {
+        return err!(HttpError::InvalidOverwrite, StatusCode::BAD_REQUEST)
+    };
+
+    if channel.get_owner().await.as_deref() != Some(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if !update.is_valid() {
+        return err!(HttpError::InvalidOverwrite, StatusCode::BAD_REQUEST);
+    }
+
+    match update.kind {
+        OverwriteKind::Role if target_id != *guild => {
+            let role = unwrap!(database().await.fetch_role(&target_id).await);
+            if role.is_none_or(|it| it.guild_id != *guild) {
+                return not_found!("Role");
+            }
+        }
+        OverwriteKind::Role => {}
+        OverwriteKind::Member => {
+            if !unwrap!(database().await.is_guild_member(guild, &target_id).await) {
+                return not_found!("Member");
+            }
+        }
+    }
+
+    let mut overwrites = channel.overwrites.clone();
+    overwrites.retain(|it| it.id != target_id);
+    overwrites.push(PermissionOverwrite {
+        id: target_id,
+        kind: update.kind,
+        allow: update.allow,
+        deny: update.deny,
+    });
+
+    update_overwrites(channel, overwrites, &clients).await
+}
+
+#[delete("/channels/{id}/overwrites/{target_id}")]
+pub async fn delete_overwrite(
+    id: String,
+    target_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelResponse> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    if channel.get_owner().await.as_deref() != Some(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if !channel.overwrites.iter().any(|it| it.id == target_id) {
+        return not_found!("Overwrite");
+    }
+
+    let mut overwrites = channel.overwrites.clone();
+    overwrites.retain(|it| it.id != target_id);
+
+    update_overwrites(channel, overwrites, &clients).await
+}
+
+/// Saves the overwrites of a channel, and sends the update to everyone who could see it
+/// before or can see it now.
+async fn update_overwrites(
+    channel: Channel,
+    overwrites: Vec<PermissionOverwrite>,
+    clients: &ClientHolder,
+) -> ResponseResult<ChannelResponse> {
+    let ChannelLocation::Guild { guild } = &channel.location else {
+        return err!(HttpError::InvalidOverwrite, StatusCode::BAD_REQUEST)
+    };
+
+    let Some(permissions) = unwrap!(GuildPermissions::fetch(guild).await) else {
+        return not_found!("Guild")
+    };
+
+    let mut users = permissions.viewers(&channel.overwrites);
+
+    let updated = database()
+        .await
+        .set_channel_overwrites(&channel.id, &overwrites)
+        .await;
+
+    let Some(channel) = unwrap!(updated) else {
+        return not_found!("Channel")
+    };
+
+    users.extend(permissions.viewers(&channel.overwrites));
+    users.sort();
+    users.dedup();
+
+    let resp = ChannelResponse::from_channel(channel);
+
+    with_lock!(clients).dispatch_users(users, &Event::ChannelUpdate(resp.clone()));
+
+    ok!(resp)
+}
+
+/// Whether a user may change or delete a channel.
+async fn can_manage(channel: &Channel, users: &[String], user_id: &str) -> bool {
+    if !users.iter().any(|it| it == user_id) {
+        return false;
+    }
+    match channel.location {
+        ChannelLocation::Dm { .. } => true,
+        ChannelLocation::Guild { .. } | ChannelLocation::Thread { .. } => {
+            channel.get_owner().await.as_deref() == Some(user_id)
+        }
+    }
+}
+
+/// Exports the whole history of a channel as JSON, plain text or HTML.
+#[get("/channels/{id}/transcript")]
+pub async fn transcript(
+    #[header = "Authentication"] token: String,
+    id: String,
+    #[filter = "warp::query"] query: TranscriptQuery,
+) -> Result<impl Reply, Rejection> {
+    let channel = match fetch_readable(token, id).await?.success() {
+        Ok(channel) => channel,
+        Err(err) => return Ok(err.into_response()),
+    };
+
+    let format = query.format.unwrap_or_default();
+    let filename = format!("{}-{}.{}", channel.name, channel.id, format.extension());
+
+    let body = Body::wrap_stream(Transcript::new(channel, format).stream());
+
+    let mut response = warp::reply::Response::new(body);
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, header_value(format.content_type()));
+    headers.insert(CONTENT_DISPOSITION, content_disposition("attachment", &filename));
+
+    Ok(response)
+}
+
+/// Fetches a channel, checking that the user can see it.
+async fn fetch_readable(token: String, id: String) -> ResponseResult<Channel> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    if !channel.get_users().await.unwrap_or(vec![]).contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    ok!(channel)
+}
+
+#[get("/channels/{id}/pins")]
+pub async fn fetch_pins(
+    #[header = "Authentication"] token: String,
+    id: String,
+) -> ResponseResult<Vec<MessageResponse>> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    if !channel.get_users().await.unwrap_or(vec![]).contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let messages = unwrap!(database().await.fetch_pinned_messages(id.clone()).await);
+
+    ok!(MessageResponse::from_messages(messages, &channel).await)
+}
+
+#[put("/channels/{id}/pins/{message_id}")]
+pub async fn pin(
+    id: String,
+    message_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelPinsResponse> {
+    set_pinned(token, id, message_id, true, clients).await
+}
+
+#[delete("/channels/{id}/pins/{message_id}")]
+pub async fn unpin(
+    id: String,
+    message_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelPinsResponse> {
+    set_pinned(token, id, message_id, false, clients).await
+}
+
+async fn set_pinned(
+    token: String,
+    id: String,
+    message_id: String,
+    pinned: bool,
+    clients: ClientHolder,
+) -> ResponseResult<ChannelPinsResponse> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if let Some(owner) = channel.get_owner().await {
+        if owner != user.id {
+            return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+        }
+    }
+
+    let Some(message) = unwrap!(database().await.fetch_message(message_id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    if message.channel_id != channel.id {
+        return not_found!("Message");
+    }
+
+    if message.pinned_at.is_some() == pinned {
+        return ok!(ChannelPinsResponse {
+            channel_id: channel.id,
+            message_id: message.id,
+            pinned_at: message.pinned_at,
+        });
+    }
+
+    if pinned && unwrap!(database().await.count_pinned_messages(id.clone()).await) >= MAX_PINS {
+        return err!(HttpError::TooManyPins(MAX_PINS), StatusCode::BAD_REQUEST);
+    }
+
+    let Some(message) = unwrap!(message.set_pinned(pinned).await) else {
+        return not_found!("Message")
+    };
+
+    let resp = ChannelPinsResponse {
+        channel_id: channel.id,
+        message_id: message.id,
+        pinned_at: message.pinned_at,
+    };
+
+    with_lock!(clients).dispatch_users(users, &Event::ChannelPinsUpdate(resp.clone()));
+
+    ok!(resp)
+}
+
+#[post("/channels/{id}/threads")]
+pub async fn create_thread(
+    #[header = "Authentication"] token: String,
+    id: String,
+    #[json] create: ThreadCreate,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ChannelResponse> {
+    let user = with_login!(token);
+
+    if let Err(err) = validation::name(Field::ChannelName, &create.name) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    let auto_archive_after = create.auto_archive_after.unwrap_or(DEFAULT_AUTO_ARCHIVE);
+
+    if !AUTO_ARCHIVE_DURATIONS.contains(&auto_archive_after) {
+        return err!(HttpError::InvalidAutoArchive, StatusCode::BAD_REQUEST);
+    }
+
+    let Some(parent) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let ChannelLocation::Guild { .. } = parent.location else {
+        return err!(HttpError::InvalidThread, StatusCode::BAD_REQUEST)
+    };
+
+    let users = parent.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let Some(origin) = unwrap!(database().await.fetch_message(create.message_id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    if origin.channel_id != parent.id {
+        return err!(HttpError::InvalidThread, StatusCode::BAD_REQUEST);
+    }
+
+    if unwrap!(database().await.fetch_thread_by_origin(&origin.id).await).is_some() {
+        return err!(HttpError::ThreadExists, StatusCode::CONFLICT);
+    }
+
+    let thread = unwrap!(
+        Channel::new_thread(
+            &create.name,
+            parent.id.clone(),
+            origin.id.clone(),
+            user.id.clone(),
+            auto_archive_after
+        )
+        .insert()
+        .await
+    );
+
+    let resp = ChannelResponse::from_channel(thread);
+
+    with_lock!(clients).dispatch_users(users, &Event::ThreadCreate(resp.clone()));
+
+    ok!(resp)
+}
+
+/// Fetches the threads of a channel that are not archived.
+#[get("/channels/{id}/threads")]
+pub async fn fetch_threads(
+    #[header = "Authentication"] token: String,
+    id: String,
+) -> ResponseResult<Vec<ChannelResponse>> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    if !channel.get_users().await.unwrap_or(vec![]).contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let threads = unwrap!(database().await.fetch_active_threads(&channel.id).await);
+
+    ok!(threads.into_iter().map(ChannelResponse::from_channel).collect())
+}
@@ -0,0 +1,118 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        command::{Command, CommandCreate},
+        error::ResponseResult,
+    },
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    HttpError,
+};
+
+pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let register = register();
+
+    let fetch_all = fetch_all();
+
+    let delete = delete();
+
+    register
+        .or(fetch_all)
+        .or(delete)
+        .map(Reply::into_response)
+        .boxed()
+}
+
+const MAX_COMMANDS: u64 = 100;
+
+/// Registers a command of the bot in a guild, replacing its command with the same name.
+#[put("/guilds/{id}/commands")]
+pub async fn register(
+    #[header = "Authentication"] token: String,
+    id: String,
+    #[json] create: CommandCreate,
+) -> ResponseResult<Command> {
+    let bot = with_login!(token);
+
+    if !bot.bot {
+        return err!(HttpError::NotABot, StatusCode::FORBIDDEN);
+    }
+
+    if !unwrap!(database().await.is_guild_member(&id, &bot.id).await) {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let command = match create.validate(&id, &bot.id) {
+        Ok(command) => command,
+        Err(err) => return err!(HttpError::Command(err), StatusCode::BAD_REQUEST),
+    };
+
+    let count = database()
+        .await
+        .count_other_bot_commands(&id, &bot.id, &command.name)
+        .await;
+    if unwrap!(count) >= MAX_COMMANDS {
+        return err!(
+            HttpError::TooManyCommands(MAX_COMMANDS),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    ok!(unwrap!(
+        database().await.upsert_command(command.clone()).await
+    ))
+}
+
+#[get("/guilds/{id}/commands")]
+pub async fn fetch_all(
+    #[header = "Authentication"] token: String,
+    id: String,
+) -> ResponseResult<Vec<Command>> {
+    let user = with_login!(token);
+
+    if !unwrap!(database().await.is_guild_member(&id, &user.id).await) {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    ok!(unwrap!(database().await.fetch_guild_commands(&id).await))
+}
+
+/// Deletes a command. Only the bot that registered it or the owner of the guild may do so.
+#[delete("/guilds/{id}/commands/{command_id}")]
+pub async fn delete(
+    id: String,
+    command_id: String,
+    #[header = "Authentication"] token: String,
+) -> ResponseResult<Command> {
+    let user = with_login!(token);
+
+    let Some(command) = unwrap!(database().await.fetch_command(&command_id).await) else {
+        return not_found!("Command")
+    };
+
+    if command.guild_id != id {
+        return not_found!("Command");
+    }
+
+    if command.bot_id != user.id {
+        let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+            return not_found!("Guild")
+        };
+        if guild.owner_id != user.id {
+            return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+        }
+    }
+
+    unwrap!(database().await.delete_command(&command.id).await);
+
+    ok!(command)
+}
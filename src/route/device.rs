@@ -0,0 +1,214 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        channel::ChannelLocation,
+        encryption::{
+            Device, DeviceCreate, DeviceListResponse, EncryptionError, KeyBundle, KeyBundleQuery,
+            PreKeysUpload, MAX_DEVICES,
+        },
+        error::ResponseResult,
+        event::Event,
+        validation::{self, Field},
+    },
+    with_lock,
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
+};
+
+pub fn routes(
+    clients: &ClientHolder,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let create = create(clients.clone());
+
+    let fetch_all = fetch_all();
+
+    let upload_prekeys = upload_prekeys();
+
+    let delete = delete(clients.clone());
+
+    let key_bundles = key_bundles();
+
+    create
+        .or(fetch_all)
+        .or(upload_prekeys)
+        .or(delete)
+        .or(key_bundles)
+        .map(Reply::into_response)
+        .boxed()
+}
+
+/// Registers a device with its public keys, so messages in encrypted DMs can be sent to it.
+#[post("/devices")]
+pub async fn create(
+    #[header = "Authentication"] token: String,
+    #[json] create: DeviceCreate,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<Device> {
+    let user = with_login!(token);
+
+    if let Err(err) = validation::name(Field::DeviceName, &create.name) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    let devices = unwrap!(database().await.fetch_user_devices(&user.id).await);
+
+    if devices.len() >= MAX_DEVICES {
+        return err!(HttpError::TooManyDevices(MAX_DEVICES), StatusCode::BAD_REQUEST);
+    }
+
+    let (device, one_time_prekeys) = match create.validate(&user.id) {
+        Ok(it) => it,
+        Err(err) => return err!(HttpError::Encryption(err), StatusCode::BAD_REQUEST),
+    };
+
+    let inserted = device.insert(one_time_prekeys).await;
+    let device = unwrap!(inserted);
+
+    dispatch_devices(&user.id, &clients).await;
+
+    ok!(device)
+}
+
+#[get("/devices")]
+pub async fn fetch_all(
+    #[header = "Authentication"] token: String,
+) -> ResponseResult<Vec<Device>> {
+    let user = with_login!(token);
+
+    ok!(unwrap!(database().await.fetch_user_devices(&user.id).await))
+}
+
+/// Rotates the signed prekey of a device, or replenishes its one-time prekeys.
+#[post("/devices/{id}/prekeys")]
+pub async fn upload_prekeys(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[json] upload: PreKeysUpload,
+) -> ResponseResult<Device> {
+    let user = with_login!(token);
+
+    let Some(device) = unwrap!(database().await.fetch_device(&id).await) else {
+        return not_found!("Device")
+    };
+
+    if device.user_id != user.id {
+        return not_found!("Device");
+    }
+
+    if let Err(err) = upload.validate() {
+        return err!(HttpError::Encryption(err), StatusCode::BAD_REQUEST);
+    }
+
+    let updated = database()
+        .await
+        .add_prekeys(&device.id, upload.signed_prekey, upload.one_time_prekeys)
+        .await;
+
+    let Some(device) = unwrap!(updated) else {
+        return not_found!("Device")
+    };
+
+    ok!(device)
+}
+
+/// Removes a device. Messages already encrypted for it stay unreadable to its replacement.
+#[delete("/devices/{id}")]
+pub async fn delete(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<Device> {
+    let user = with_login!(token);
+
+    let Some(device) = unwrap!(database().await.fetch_device(&id).await) else {
+        return not_found!("Device")
+    };
+
+    if device.user_id != user.id {
+        return not_found!("Device");
+    }
+
+    unwrap!(database().await.delete_device(&device.id).await);
+
+    dispatch_devices(&user.id, &clients).await;
+
+    ok!(device)
+}
+
+/// Fetches the key bundles of the devices of every member of an encrypted DM.
+#[get("/channels/{id}/keys")]
+pub async fn key_bundles(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[filter = "warp::query"] query: KeyBundleQuery,
+) -> ResponseResult<Vec<KeyBundle>> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let ChannelLocation::Dm { members } = &channel.location else {
+        return err!(HttpError::Encryption(EncryptionError::OnlyDms), StatusCode::BAD_REQUEST)
+    };
+
+    if !members.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if !channel.encrypted {
+        return err!(HttpError::Encryption(EncryptionError::NotEncrypted), StatusCode::BAD_REQUEST);
+    }
+
+    let devices = unwrap!(database().await.fetch_devices_of(members).await);
+
+    let mut bundles = vec![];
+    for device in devices {
+        let one_time_prekey = if query.claim.unwrap_or(false) {
+            unwrap!(database().await.claim_prekey(&device.id).await)
+        } else {
+            None
+        };
+        bundles.push(KeyBundle::new(device, one_time_prekey));
+    }
+
+    ok!(bundles)
+}
+
+/// Sends the devices of a user to them and to the members of their encrypted DMs.
+async fn dispatch_devices(user_id: &str, clients: &ClientHolder) {
+    let database = database().await;
+    let Ok(devices) = database.fetch_user_devices(user_id).await else {
+        return;
+    };
+    let channels = database
+        .fetch_user_channels(user_id)
+        .await
+        .unwrap_or(vec![]);
+
+    let mut users = vec![user_id.to_string()];
+    for channel in channels.into_iter().filter(|it| it.encrypted) {
+        if let ChannelLocation::Dm { members } = channel.location {
+            users.extend(members.into_iter().filter(|it| it != user_id));
+        }
+    }
+    users.sort();
+    users.dedup();
+
+    let resp = DeviceListResponse {
+        user_id: user_id.to_string(),
+        devices,
+    };
+
+    with_lock!(clients).dispatch_users(users, &Event::DeviceListUpdate(resp));
+}
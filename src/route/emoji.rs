@@ -0,0 +1,225 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{
+    http::header::{CACHE_CONTROL, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS},
+    hyper::StatusCode,
+    multipart::FormData,
+    Filter, Rejection, Reply,
+};
+
+use crate::{
+    database, storage,
+    structures::{
+        emoji::{EmojiFile, GuildEmoji, GuildEmojisResponse},
+        error::ResponseResult,
+        event::Event,
+        guild::Guild,
+        user::User,
+    },
+    with_lock,
+};
+
+use super::{
+    attachment::{header_value, read_file, upload_form},
+    macros::{err, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
+};
+
+pub fn routes(
+    clients: &ClientHolder,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let create = create(clients.clone());
+
+    let fetch_all = fetch_all();
+
+    let delete = delete(clients.clone());
+
+    let image = image();
+
+    create
+        .or(fetch_all)
+        .or(delete)
+        .or(image)
+        .map(Reply::into_response)
+        .boxed()
+}
+
+const MAX_EMOJIS: u64 = 50;
+
+#[put("/guilds/{id}/emojis/{name}")]
+pub async fn create(
+    id: String,
+    name: String,
+    #[header = "Authentication"] token: String,
+    #[filter = "upload_form"] form: FormData,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<GuildEmoji> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if !GuildEmoji::is_valid_name(&name) {
+        return err!(HttpError::InvalidEmojiName, StatusCode::BAD_REQUEST);
+    }
+
+    let emojis = unwrap!(database().await.fetch_guild_emojis(&guild.id).await);
+
+    if emojis.iter().any(|it| it.name == name) {
+        return err!(HttpError::EmojiExists, StatusCode::CONFLICT);
+    }
+
+    if emojis.len() as u64 >= MAX_EMOJIS {
+        return err!(HttpError::TooManyEmojis(MAX_EMOJIS), StatusCode::BAD_REQUEST);
+    }
+
+    let file = match read_file(form).await {
+        Ok(file) => file,
+        Err(err) => return err!(HttpError::Other(err.to_string()), StatusCode::BAD_REQUEST),
+    };
+
+    let Some((_, content_type, data)) = file else {
+        return err!(HttpError::MissingAttachment, StatusCode::BAD_REQUEST)
+    };
+
+    let max = GuildEmoji::max_size();
+    if !GuildEmoji::is_valid_content_type(&content_type) || data.len() as u64 > max {
+        return err!(HttpError::InvalidEmojiImage(max), StatusCode::BAD_REQUEST);
+    }
+
+    let emoji = GuildEmoji::new(&guild.id, &name, &content_type, &user.id);
+
+    if let Err(err) = storage().put(&emoji.id, data, &content_type).await {
+        return err!(HttpError::Storage(err), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let emoji = unwrap!(emoji.clone().insert().await);
+
+    dispatch_emojis(&guild, &clients).await;
+
+    ok!(emoji)
+}
+
+#[get("/guilds/{id}/emojis")]
+pub async fn fetch_all(
+    id: String,
+    #[header = "Authentication"] token: String,
+) -> ResponseResult<GuildEmojisResponse> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    let users: Vec<User> = unwrap!(database().await.fetch_guild_users(&guild.id).await)
+        .option()
+        .unwrap_or(vec![]);
+
+    if !users.iter().any(|it| it.id == user.id) {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let emojis = unwrap!(database().await.fetch_guild_emojis(&guild.id).await);
+
+    ok!(GuildEmojisResponse {
+        guild_id: guild.id,
+        emojis,
+    })
+}
+
+#[delete("/guilds/{id}/emojis/{emoji_id}")]
+pub async fn delete(
+    id: String,
+    emoji_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<GuildEmoji> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let Some(emoji) = unwrap!(database().await.fetch_emoji(&emoji_id).await) else {
+        return not_found!("Emoji")
+    };
+
+    if emoji.guild_id != guild.id {
+        return not_found!("Emoji");
+    }
+
+    unwrap!(database().await.delete_emoji(&emoji.id).await);
+
+    // Messages may still reference the emoji, so a missing image is not fatal here.
+    let _ = storage().delete(&emoji.id).await;
+
+    dispatch_emojis(&guild, &clients).await;
+
+    ok!(emoji)
+}
+
+/// Serves emoji images without authentication, so rendered messages can link to them.
+#[get("/emojis/{id}")]
+pub async fn image(id: String) -> Result<impl Reply, Rejection> {
+    let file = match fetch_image(id).await?.success() {
+        Ok(file) => file,
+        Err(err) => return Ok(err.into_response()),
+    };
+
+    let mut response = warp::reply::Response::new(file.data.into());
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_TYPE, header_value(&file.emoji.content_type));
+    headers.insert(X_CONTENT_TYPE_OPTIONS, header_value("nosniff"));
+    headers.insert(CACHE_CONTROL, header_value("public, max-age=86400"));
+
+    Ok(response)
+}
+
+async fn fetch_image(id: String) -> ResponseResult<EmojiFile> {
+    let Some(emoji) = unwrap!(database().await.fetch_emoji(&id).await) else {
+        return not_found!("Emoji")
+    };
+
+    let data = match storage().get(&emoji.id).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return not_found!("Emoji"),
+        Err(err) => return err!(HttpError::Storage(err), StatusCode::INTERNAL_SERVER_ERROR),
+    };
+
+    ok!(EmojiFile { emoji, data })
+}
+
+/// Sends the current emoji of a guild to its members.
+async fn dispatch_emojis(guild: &Guild, clients: &ClientHolder) {
+    let database = database().await;
+    let Ok(emojis) = database.fetch_guild_emojis(&guild.id).await else {
+        return;
+    };
+    let users = database
+        .fetch_guild_users(&guild.id)
+        .await
+        .map(|it| it.option().unwrap_or(vec![]))
+        .unwrap_or(vec![]);
+
+    let resp = GuildEmojisResponse {
+        guild_id: guild.id.clone(),
+        emojis,
+    };
+
+    with_lock!(clients).dispatch_users(
+        users.into_iter().map(|it| it.id).collect(),
+        &Event::GuildEmojisUpdate(resp),
+    );
+}
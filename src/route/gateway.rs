@@ -17,6 +17,7 @@ use crate::{
         client::{Client, ClientHolder},
         event::Event,
         event::ReceivedEvent,
+        read_state::ReadState,
     },
     with_lock,
 };
@@ -55,7 +56,7 @@ async fn handle_conn(ws: WebSocket, clients: ClientHolder, mut client: Client) {
         };
 
         if msg.is_text() && let Ok(event) = serde_json::from_str::<ReceivedEvent>(msg.to_str().unwrap()) {
-            let event = handle_event(&event, client.clone(), &clients).await;
+            let event = handle_event(&event, &mut client, &clients).await;
             if event.is_none() {
                 continue;
             }
@@ -68,7 +69,7 @@ async fn handle_conn(ws: WebSocket, clients: ClientHolder, mut client: Client) {
 
 async fn handle_event(
     event: &ReceivedEvent,
-    mut client: Client,
+    client: &mut Client,
     clients: &ClientHolder,
 ) -> Option<Event> {
     match event {
@@ -91,5 +92,26 @@ async fn handle_event(
             }
             Some(Event::HandshakeComplete { user })
         }
+        ReceivedEvent::Ack {
+            channel_id,
+            message_id,
+        } => {
+            let user_id = client.user_id.clone()?;
+            let channel = database()
+                .await
+                .fetch_channel(channel_id.clone())
+                .await
+                .ok()??;
+            if !channel.can_view(&user_id).await {
+                return None;
+            }
+            let state = ReadState::ack(&user_id, &channel, message_id.clone())
+                .await
+                .ok()?
+                .ok()?;
+            let event = Event::ReadStateUpdate(state);
+            with_lock!(clients).dispatch_user_except(&user_id, client, &event);
+            Some(event)
+        }
     }
 }
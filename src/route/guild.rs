@@ -3,14 +3,15 @@
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
 use rweb::*;
-use warp::{Filter, Rejection, Reply};
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
 
 use crate::{
     database,
-    route::macros::{ok, unwrap, with_login},
+    route::macros::{err, ok, unwrap, with_login},
     structures::{
-        error::ResponseResult,
+        error::{HttpError, ResponseResult},
         guild::{Guild, GuildCreate, GuildResponse},
+        validation::{self, Field},
     },
 };
 
@@ -29,6 +30,10 @@ pub async fn create(
 ) -> ResponseResult<GuildResponse> {
     let user = with_login!(token);
 
+    if let Err(err) = validation::name(Field::GuildName, &create.name) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
     let guild = unwrap!(Guild::new(&create.name, &user.id).insert().await);
 
     let resp = GuildResponse::new(guild, user);
@@ -0,0 +1,224 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        component::{ActionRow, ComponentUse},
+        embed::Embed,
+        emoji::GuildEmoji,
+        error::ResponseResult,
+        event::Event,
+        interaction::{Interaction, InteractionCallback, InteractionCreate, MessageInteraction},
+        markdown,
+        mention::Mentions,
+        message::{Message, MessageResponse},
+        validation,
+    },
+    with_lock,
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
+};
+
+pub fn routes(
+    clients: &ClientHolder,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let create = create(clients.clone());
+
+    let callback = callback(clients.clone());
+
+    let use_component = use_component(clients.clone());
+
+    create
+        .or(callback)
+        .or(use_component)
+        .map(Reply::into_response)
+        .boxed()
+}
+
+/// Invokes a command in a channel, sending the interaction to the bot that registered it.
+#[post("/interactions")]
+pub async fn create(
+    #[header = "Authentication"] token: String,
+    #[json] create: InteractionCreate,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<Interaction> {
+    let user = with_login!(token);
+
+    let Some(command) = unwrap!(database().await.fetch_command(&create.command_id).await) else {
+        return not_found!("Command")
+    };
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(create.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    // The command must belong to the channel's guild, and its bot must still be a member.
+    let guild = channel.get_guild().await.option();
+    if guild.as_ref() != Some(&command.guild_id) || !users.contains(&command.bot_id) {
+        return not_found!("Command");
+    }
+
+    let validated = command.validate_options(&create.options).await;
+    if let Err(err) = unwrap!(validated) {
+        return err!(HttpError::Command(err), StatusCode::BAD_REQUEST);
+    }
+
+    let interaction = Interaction::new(&command, channel.id, user, create.options);
+    let interaction = unwrap!(interaction.clone().insert().await);
+
+    with_lock!(clients).dispatch_users(
+        vec![interaction.bot_id.clone()],
+        &Event::InteractionCreate(interaction.clone()),
+    );
+
+    ok!(interaction)
+}
+
+/// Responds to an interaction with a message from the bot. Interactions may be responded to once.
+#[post("/interactions/{id}/callback")]
+pub async fn callback(
+    #[header = "Authentication"] token: String,
+    id: String,
+    #[json] callback: InteractionCallback,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<MessageResponse> {
+    let bot = with_login!(token);
+
+    let Some(interaction) = unwrap!(database().await.fetch_interaction(&id).await) else {
+        return not_found!("Interaction")
+    };
+
+    if interaction.bot_id != bot.id {
+        return not_found!("Interaction");
+    }
+
+    if callback.content.is_empty() && callback.embeds.is_empty() {
+        return err!(HttpError::MessageContentEmpty, StatusCode::BAD_REQUEST);
+    }
+
+    let mut embeds = callback.embeds.clone();
+    if let Err(err) = Embed::validate(&mut embeds) {
+        return err!(HttpError::Embed(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = ActionRow::validate(&callback.components) {
+        return err!(HttpError::Component(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = validation::content(&callback.content) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = markdown::parse(&callback.content) {
+        return err!(HttpError::Markdown(err), StatusCode::BAD_REQUEST);
+    }
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(interaction.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&bot.id) || !channel.can_send(&bot.id).await {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let content = GuildEmoji::resolve_in(&callback.content, &channel).await;
+
+    // Resolved emoji are longer than their shortcodes.
+    if let Err(err) = validation::content(&content) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    let mentions = Mentions::parse(&content)
+        .resolve(&bot.id, &users, &channel)
+        .await;
+
+    let message = Message::new(channel.id.clone(), bot.id.clone(), content)
+        .with_mentions(mentions)
+        .with_embeds(embeds)
+        .with_components(callback.components.clone())
+        .with_interaction(Some(MessageInteraction::from(&interaction)));
+
+    // Claimed last, so only a response that was sent uses up the interaction.
+    let claimed = database().await.claim_interaction(&interaction.id).await;
+    if !unwrap!(claimed) {
+        return err!(HttpError::InteractionResponded, StatusCode::CONFLICT);
+    }
+
+    let inserted = message.clone().insert().await;
+    if inserted.is_err() {
+        let _ = database().await.release_interaction(&interaction.id).await;
+    }
+    let message = unwrap!(inserted);
+
+    let published = MessageResponse::publish(message, channel, bot, users, &clients).await;
+
+    ok!(unwrap!(published))
+}
+
+/// Clicks a button or picks values in a select menu, sending the interaction to the message's bot.
+///
+/// The bot may respond through `/interactions/{id}/callback`, as for commands.
+#[post("/messages/{id}/components/{custom_id}")]
+pub async fn use_component(
+    id: String,
+    custom_id: String,
+    #[header = "Authentication"] token: String,
+    #[json] component_use: ComponentUse,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<Interaction> {
+    let user = with_login!(token);
+
+    let Some(message) = unwrap!(database().await.fetch_message(id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(message.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    // Components are only delivered while the bot that sent them can still see the channel.
+    let Some(bot_id) = message.author_id.clone().filter(|it| users.contains(it)) else {
+        return not_found!("Component")
+    };
+
+    let Some(component) = ActionRow::find(&message.components, &custom_id) else {
+        return not_found!("Component")
+    };
+
+    if let Err(err) = component.check_use(&component_use.values) {
+        return err!(HttpError::Component(err), StatusCode::BAD_REQUEST);
+    }
+
+    let interaction =
+        Interaction::component(&message, bot_id, user, custom_id, component_use.values);
+    let interaction = unwrap!(interaction.clone().insert().await);
+
+    with_lock!(clients).dispatch_users(
+        vec![interaction.bot_id.clone()],
+        &Event::ComponentInteraction(interaction.clone()),
+    );
+
+    ok!(interaction)
+}
@@ -0,0 +1,61 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use chrono::Utc;
+use rweb::*;
+use warp::{Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{error::ResponseResult, mention::MentionFetch, message::MessageResponse},
+};
+
+use super::macros::{ok, unwrap, with_login};
+
+pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    fetch()
+}
+
+#[get("/mentions")]
+pub async fn fetch(
+    #[header = "Authentication"] token: String,
+    #[filter = "warp::query"] query: MentionFetch,
+) -> ResponseResult<Vec<MessageResponse>> {
+    let user = with_login!(token);
+
+    let max = query.max.unwrap_or(25).min(25);
+    let before = query.before.unwrap_or_else(|| Utc::now().to_rfc3339());
+
+    let ids = unwrap!(
+        database()
+            .await
+            .fetch_mentions_before(user.id.clone(), before.clone(), max)
+            .await
+    );
+
+    let mut out = vec![];
+
+    for id in ids {
+        let Some(message) = unwrap!(database().await.fetch_message(id.clone()).await) else {
+            continue;
+        };
+
+        let Some(channel) = unwrap!(
+            database()
+                .await
+                .fetch_channel(message.channel_id.clone())
+                .await
+        ) else {
+            continue;
+        };
+
+        if !channel.can_view(&user.id).await {
+            continue;
+        }
+
+        out.push(MessageResponse::from_message(message, channel).await);
+    }
+
+    ok!(out)
+}
@@ -6,11 +6,26 @@ use rweb::*;
 use warp::{hyper::StatusCode, Filter, Rejection, Reply};
 
 use crate::{
-    database, map_async,
+    database,
     structures::{
+        attachment::Attachment,
+        channel::Channel,
+        component::ActionRow,
+        embed::Embed,
+        emoji::GuildEmoji,
+        encryption::EncryptionError,
         error::ResponseResult,
         event::Event,
-        message::{Message, MessageCreate, MessageFetchAfter, MessageFetchBefore, MessageResponse},
+        markdown,
+        mention::Mentions,
+        message::{
+            Message, MessageBulkDelete, MessageCreate, MessageDeleteBulkResponse,
+            MessageDeleteResponse, MessageFetchAfter, MessageFetchBefore, MessageFetchSingle,
+            MessageResponse,
+        },
+        poll::{PollCreate, PollTally},
+        reaction::{Reaction, ReactionResponse},
+        validation,
     },
     with_lock,
 };
@@ -20,12 +35,31 @@ use super::{
     ClientHolder, HttpError,
 };
 
+/// The routes are boxed, as polling the deeply nested filter otherwise
+/// overflows the stack of the runtime's worker threads in debug builds.
 pub fn routes(
     clients: &ClientHolder,
 ) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
-    create(clients.clone()).or(fetch_single()).or(fetch_before()).or(fetch_after())
+    create(clients.clone())
+        .or(fetch_single())
+        .or(fetch_before())
+        .or(fetch_after())
+        .or(delete(clients.clone()))
+        .or(delete_bulk(clients.clone()))
+        .or(add_reaction(clients.clone()))
+        .or(remove_reaction(clients.clone()))
+        .or(vote(clients.clone()))
+        .or(unvote(clients.clone()))
+        .map(Reply::into_response)
+        .boxed()
 }
 
+const MAX_BULK_DELETE: usize = 100;
+const MAX_ATTACHMENTS: usize = 10;
+const MAX_NONCE_LENGTH: usize = 64;
+const MIN_EXPIRY: u64 = 5;
+const MAX_EXPIRY: u64 = 7 * 24 * 60 * 60;
+
 #[post("/messages")]
 pub async fn create(
     #[header = "Authentication"] token: String,
@@ -34,41 +68,222 @@ pub async fn create(
 ) -> ResponseResult<MessageResponse> {
     let user = with_login!(token);
 
-    if create.content.is_empty() {
+    if let Some(nonce) = &create.nonce {
+        if nonce.is_empty() || nonce.chars().count() > MAX_NONCE_LENGTH {
+            return err!(HttpError::InvalidNonce(MAX_NONCE_LENGTH), StatusCode::BAD_REQUEST);
+        }
+
+        if let Some(id) = unwrap!(database().await.fetch_nonce(&user.id, nonce).await) {
+            if let Some(resp) = fetch_sent(id).await {
+                return ok!(resp);
+            }
+        }
+    }
+
+    if create.content.is_empty()
+        && create.attachments.is_empty()
+        && create.poll.is_none()
+        && create.embeds.is_empty()
+        && create.encrypted.is_none()
+    {
         return err!(HttpError::MessageContentEmpty, StatusCode::BAD_REQUEST);
     }
 
+    if (!create.embeds.is_empty() || !create.components.is_empty()) && !user.bot {
+        return err!(HttpError::NotABot, StatusCode::FORBIDDEN);
+    }
+
+    let mut embeds = create.embeds.clone();
+    if let Err(err) = Embed::validate(&mut embeds) {
+        return err!(HttpError::Embed(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = ActionRow::validate(&create.components) {
+        return err!(HttpError::Component(err), StatusCode::BAD_REQUEST);
+    }
+
+    let poll = match create.poll.as_ref().map(PollCreate::validate) {
+        Some(Err(err)) => return err!(HttpError::Poll(err), StatusCode::BAD_REQUEST),
+        Some(Ok(poll)) => Some(poll),
+        None => None,
+    };
+
+    if let Some(expires_in) = create.expires_in {
+        if !(MIN_EXPIRY..=MAX_EXPIRY).contains(&expires_in) {
+            let (min, max) = (MIN_EXPIRY, MAX_EXPIRY);
+            return err!(HttpError::InvalidExpiry { min, max }, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    if let Err(err) = validation::content(&create.content) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = markdown::parse(&create.content) {
+        return err!(HttpError::Markdown(err), StatusCode::BAD_REQUEST);
+    }
+
     let Some(channel) = unwrap!(database().await.fetch_channel(create.channel_id.clone()).await) else {
         return not_found!("Channel")
     };
 
     let users = channel.get_users().await.unwrap_or(vec![]);
 
-    if !users.contains(&user.id) {
+    if !users.contains(&user.id) || !channel.can_send(&user.id).await {
         return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
     }
 
-    let message = unwrap!(
-        Message::new(
-            create.channel_id.clone(),
-            user.id.clone(),
-            create.content.clone()
-        )
-        .insert()
-        .await
+    match (&create.encrypted, channel.encrypted) {
+        (Some(encrypted), true) => {
+            let plaintext = !create.content.is_empty()
+                || !create.attachments.is_empty()
+                || create.poll.is_some()
+                || !create.embeds.is_empty()
+                || !create.components.is_empty();
+            if plaintext {
+                let err = HttpError::Encryption(EncryptionError::Plaintext);
+                return err!(err, StatusCode::BAD_REQUEST);
+            }
+            if let Err(err) = unwrap!(encrypted.validate(&user.id, &users).await) {
+                return err!(HttpError::Encryption(err), StatusCode::BAD_REQUEST);
+            }
+        }
+        (None, true) => {
+            let err = HttpError::Encryption(EncryptionError::Required);
+            return err!(err, StatusCode::BAD_REQUEST);
+        }
+        (Some(_), false) => {
+            let err = HttpError::Encryption(EncryptionError::NotEncrypted);
+            return err!(err, StatusCode::BAD_REQUEST);
+        }
+        (None, false) => {}
+    }
+
+    if let Some(reply_to) = &create.reply_to {
+        let Some(reply) = unwrap!(database().await.fetch_message(reply_to.clone()).await) else {
+            return not_found!("Referenced message")
+        };
+
+        if reply.channel_id != channel.id {
+            return err!(HttpError::InvalidReply, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let mut attachment_ids: Vec<String> = vec![];
+    for id in &create.attachments {
+        if !attachment_ids.contains(id) {
+            attachment_ids.push(id.clone());
+        }
+    }
+
+    if attachment_ids.len() > MAX_ATTACHMENTS {
+        return err!(HttpError::InvalidAttachment, StatusCode::BAD_REQUEST);
+    }
+
+    let mut attachments = unwrap!(
+        database()
+            .await
+            .fetch_unsent_attachments(attachment_ids.clone(), user.id.clone())
+            .await
     );
 
-    let resp = MessageResponse::from(message, channel, Some(user));
+    if attachments.len() != attachment_ids.len() {
+        return err!(HttpError::InvalidAttachment, StatusCode::BAD_REQUEST);
+    }
 
-    with_lock!(clients).dispatch_users(users, &Event::MessageCreate(resp.clone()));
+    attachments.sort_by_key(|it| attachment_ids.iter().position(|id| *id == it.id));
 
-    ok!(resp)
+    let content = GuildEmoji::resolve_in(&create.content, &channel).await;
+
+    // Resolved emoji are longer than their shortcodes.
+    if let Err(err) = validation::content(&content) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    let mentions = Mentions::parse(&content)
+        .resolve(&user.id, &users, &channel)
+        .await;
+
+    let message = Message::new(create.channel_id.clone(), user.id.clone(), content)
+        .with_reply_to(create.reply_to.clone())
+        .with_mentions(mentions)
+        .with_attachments(attachments)
+        .with_embeds(embeds)
+        .with_components(create.components.clone())
+        .with_encrypted(create.encrypted.clone())
+        .with_nonce(create.nonce.clone())
+        .with_expiry(create.expires_in)
+        .with_poll(poll);
+
+    if let Some(nonce) = &create.nonce {
+        let reserved = database()
+            .await
+            .reserve_nonce(&user.id, nonce, &message.id)
+            .await;
+        if let Some(id) = unwrap!(reserved) {
+            let Some(resp) = fetch_sent(id).await else {
+                return err!(HttpError::DuplicateNonce, StatusCode::CONFLICT);
+            };
+            return ok!(resp);
+        }
+    }
+
+    // Attachments are taken before the message is inserted, so no other message can take them.
+    let attached = database()
+        .await
+        .attach_attachments(
+            attachment_ids.clone(),
+            &user.id,
+            &message.id,
+            &message.channel_id,
+        )
+        .await;
+    if !attached
+        .as_ref()
+        .is_ok_and(|it| *it == attachment_ids.len() as u64)
+    {
+        abandon(&message, &user.id, create.nonce.as_deref()).await;
+    }
+    if unwrap!(attached) != attachment_ids.len() as u64 {
+        return err!(HttpError::InvalidAttachment, StatusCode::BAD_REQUEST);
+    }
+
+    let inserted = message.clone().insert().await;
+    if inserted.is_err() {
+        abandon(&message, &user.id, create.nonce.as_deref()).await;
+    }
+    let message = unwrap!(inserted);
+
+    let published = MessageResponse::publish(message, channel, user, users, &clients).await;
+
+    ok!(unwrap!(published))
+}
+
+/// Releases the nonce and attachments taken for a message that could not be sent.
+async fn abandon(message: &Message, author_id: &str, nonce: Option<&str>) {
+    let database = database().await;
+    let _ = database.detach_attachments(&message.id).await;
+    if let Some(nonce) = nonce {
+        let _ = database.release_nonce(author_id, nonce, &message.id).await;
+    }
+}
+
+/// Fetches a message that was already sent with the same nonce.
+async fn fetch_sent(id: String) -> Option<MessageResponse> {
+    let database = database().await;
+    let message = database.fetch_message(id).await.ok()??;
+    let channel = database
+        .fetch_channel(message.channel_id.clone())
+        .await
+        .ok()??;
+    Some(MessageResponse::from_message(message, channel).await)
 }
 
 #[get("/messages/{id}")]
 pub async fn fetch_single(
     #[header = "Authentication"] token: String,
-    id: String
+    id: String,
+    #[filter = "warp::query"] query: MessageFetchSingle,
 ) -> ResponseResult<MessageResponse> {
     let user = with_login!(token);
 
@@ -86,7 +301,9 @@ pub async fn fetch_single(
         return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
     }
 
-    ok!(MessageResponse::from_message(message, channel).await)
+    ok!(MessageResponse::from_message(message, channel)
+        .await
+        .with_render(query.render))
 }
 
 #[get("/messages")]
@@ -115,13 +332,11 @@ pub async fn fetch_before(
             .await
     );
 
-    let channel = &channel;
-
-    let mut out = vec![];
-    map_async!(messages, out, |it| MessageResponse::from_message(
-        it,
-        channel.clone()
-    ));
+    let out: Vec<_> = MessageResponse::from_messages(messages, &channel)
+        .await
+        .into_iter()
+        .map(|it| it.with_render(query.render))
+        .collect();
 
     ok!(out)
 }
@@ -152,13 +367,278 @@ pub async fn fetch_after(
             .await
     );
 
-    let channel = &channel;
-
-    let mut out = vec![];
-    map_async!(messages, out, |it| MessageResponse::from_message(
-        it,
-        channel.clone()
-    ));
+    let out: Vec<_> = MessageResponse::from_messages(messages, &channel)
+        .await
+        .into_iter()
+        .map(|it| it.with_render(query.render))
+        .collect();
 
     ok!(out)
 }
+
+#[delete("/messages/{id}")]
+pub async fn delete(
+    #[header = "Authentication"] token: String,
+    id: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<MessageDeleteResponse> {
+    let user = with_login!(token);
+
+    let Some(message) = unwrap!(database().await.fetch_message(id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(message.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if message.author_id.as_ref() != Some(&user.id) && channel.get_owner().await != Some(user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if !unwrap!(message.delete().await) {
+        return not_found!("Message");
+    }
+
+    Channel::archive_orphaned_threads(vec![message.id.clone()], &clients).await;
+
+    let resp = MessageDeleteResponse {
+        id: message.id,
+        channel_id: message.channel_id,
+    };
+
+    with_lock!(clients).dispatch_users(users, &Event::MessageDelete(resp.clone()));
+
+    ok!(resp)
+}
+
+#[post("/messages/bulk-delete")]
+pub async fn delete_bulk(
+    #[header = "Authentication"] token: String,
+    #[json] delete: MessageBulkDelete,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<MessageDeleteBulkResponse> {
+    let user = with_login!(token);
+
+    if delete.ids.len() > MAX_BULK_DELETE {
+        return err!(HttpError::TooManyMessages(MAX_BULK_DELETE), StatusCode::BAD_REQUEST);
+    }
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(delete.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let messages = unwrap!(
+        database()
+            .await
+            .fetch_messages_in(delete.channel_id.clone(), delete.ids.clone())
+            .await
+    );
+
+    if messages.is_empty() {
+        return not_found!("Messages");
+    }
+
+    let owner = channel.get_owner().await == Some(user.id.clone());
+
+    if !owner && messages.iter().any(|it| it.author_id.as_ref() != Some(&user.id)) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let ids: Vec<String> = messages.iter().map(|it| it.id.clone()).collect();
+
+    unwrap!(
+        database()
+            .await
+            .delete_messages(delete.channel_id.clone(), ids.clone())
+            .await
+    );
+
+    for message in messages {
+        Attachment::remove_all(&message.attachments).await;
+    }
+
+    Channel::archive_orphaned_threads(ids.clone(), &clients).await;
+
+    let resp = MessageDeleteBulkResponse {
+        ids,
+        channel_id: channel.id,
+    };
+
+    with_lock!(clients).dispatch_users(users, &Event::MessageDeleteBulk(resp.clone()));
+
+    ok!(resp)
+}
+
+#[put("/messages/{id}/reactions/{emoji}")]
+pub async fn add_reaction(
+    id: String,
+    emoji: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ReactionResponse> {
+    let user = with_login!(token);
+
+    let Some(emoji) = Reaction::decode_emoji(&emoji) else {
+        return err!(HttpError::InvalidEmoji, StatusCode::BAD_REQUEST)
+    };
+
+    let Some(message) = unwrap!(database().await.fetch_message(id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(message.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let reaction = Reaction::new(message.id, emoji, user.id);
+
+    let resp = ReactionResponse::new(reaction.clone(), channel.id);
+
+    if unwrap!(reaction.insert().await) {
+        with_lock!(clients).dispatch_users(users, &Event::ReactionAdd(resp.clone()));
+    }
+
+    ok!(resp)
+}
+
+#[delete("/messages/{id}/reactions/{emoji}")]
+pub async fn remove_reaction(
+    id: String,
+    emoji: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ReactionResponse> {
+    let user = with_login!(token);
+
+    let Some(emoji) = Reaction::decode_emoji(&emoji) else {
+        return err!(HttpError::InvalidEmoji, StatusCode::BAD_REQUEST)
+    };
+
+    let Some(message) = unwrap!(database().await.fetch_message(id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(message.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let reaction = Reaction::new(message.id, emoji, user.id);
+
+    let resp = ReactionResponse::new(reaction.clone(), channel.id);
+
+    if unwrap!(reaction.delete().await) {
+        with_lock!(clients).dispatch_users(users, &Event::ReactionRemove(resp.clone()));
+    }
+
+    ok!(resp)
+}
+
+#[put("/messages/{id}/poll/{option}")]
+pub async fn vote(
+    id: String,
+    option: u32,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<PollTally> {
+    set_vote(token, id, option, true, clients).await
+}
+
+#[delete("/messages/{id}/poll/{option}")]
+pub async fn unvote(
+    id: String,
+    option: u32,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<PollTally> {
+    set_vote(token, id, option, false, clients).await
+}
+
+async fn set_vote(
+    token: String,
+    id: String,
+    option: u32,
+    voted: bool,
+    clients: ClientHolder,
+) -> ResponseResult<PollTally> {
+    let user = with_login!(token);
+
+    let Some(message) = unwrap!(database().await.fetch_message(id.clone()).await) else {
+        return not_found!("Message")
+    };
+
+    let Some(poll) = &message.poll else {
+        return err!(HttpError::NotAPoll, StatusCode::BAD_REQUEST)
+    };
+
+    if option as usize >= poll.options.len() {
+        return err!(HttpError::InvalidPollOption, StatusCode::BAD_REQUEST);
+    }
+
+    if poll.is_closed() {
+        return err!(HttpError::PollClosed, StatusCode::BAD_REQUEST);
+    }
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(message.channel_id.clone()).await) else {
+        return not_found!("Channel")
+    };
+
+    let users = channel.get_users().await.unwrap_or(vec![]);
+
+    if !users.contains(&user.id) {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let changed = if voted {
+        database()
+            .await
+            .create_poll_vote(&message.id, option, &user.id, !poll.multi_select)
+            .await
+    } else {
+        database()
+            .await
+            .delete_poll_vote(&message.id, option, &user.id)
+            .await
+    };
+
+    let changed = match changed {
+        Err(err) if database::is_duplicate_key(&err) => {
+            return err!(HttpError::VoteConflict, StatusCode::CONFLICT);
+        }
+        changed => unwrap!(changed),
+    };
+
+    let Some(tally) = unwrap!(PollTally::fetch(&message).await) else {
+        return err!(HttpError::NotAPoll, StatusCode::BAD_REQUEST)
+    };
+
+    if changed {
+        with_lock!(clients).dispatch_users(users, &Event::PollUpdate(tally.clone()));
+    }
+
+    ok!(tally)
+}
@@ -2,11 +2,22 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+mod attachment;
+mod bot;
 mod channel;
+mod command;
+mod device;
+mod emoji;
 mod gateway;
 mod guild;
+mod interaction;
 mod macros;
+mod mention;
 mod message;
+mod read_state;
+mod role;
+mod scheduled_message;
+mod search;
 mod user;
 
 use reqwest::StatusCode;
@@ -14,26 +25,49 @@ use rweb::openapi;
 use std::net::Ipv4Addr;
 use std::{collections::HashMap, sync::Arc};
 use tokio::sync::Mutex;
-use warp::reject::{MethodNotAllowed, MissingHeader};
+use warp::reject::{MethodNotAllowed, MissingHeader, PayloadTooLarge};
 use warp::ws::MissingConnectionUpgrade;
-use warp::{Filter, Rejection};
+use warp::{Filter, Rejection, Reply};
 
+use crate::structures::attachment::Attachment;
 use crate::structures::client::{ClientHolder, Clients};
 use crate::structures::error::{HttpError, ResponseResult};
 use crate::structures::response::Response;
+use crate::task;
 
 use self::macros::{err, not_found};
 
 pub async fn init() {
     let clients: ClientHolder = Arc::new(Mutex::new(Clients(HashMap::new())));
 
+    task::spawn_all(&clients);
+
     //TODO: Figure out what to do with spec
     let (_spec, filter) = openapi::spec().build(|| {
-        gateway::routes(&clients)
+        // Boxed in groups, as one deeply nested filter overflows the stack in debug builds.
+        let core = gateway::routes(&clients)
             .or(message::routes(&clients))
             .or(channel::routes(&clients))
             .or(user::routes())
             .or(guild::routes())
+            .map(Reply::into_response)
+            .boxed();
+
+        let extensions = emoji::routes(&clients)
+            .or(bot::routes(&clients))
+            .or(command::routes())
+            .or(interaction::routes(&clients))
+            .or(device::routes(&clients))
+            .or(role::routes(&clients))
+            .map(Reply::into_response)
+            .boxed();
+
+        core.or(extensions)
+            .or(mention::routes())
+            .or(attachment::routes())
+            .or(search::routes())
+            .or(read_state::routes(&clients))
+            .or(scheduled_message::routes())
             .recover(recover)
             .with(warp::cors().allow_any_origin())
     });
@@ -51,6 +85,12 @@ async fn recover(rejection: Rejection) -> ResponseResult<()> {
             StatusCode::BAD_REQUEST
         );
     }
+    if rejection.find::<PayloadTooLarge>().is_some() {
+        return err!(
+            HttpError::AttachmentTooLarge(Attachment::max_size()),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
     if rejection.find::<MethodNotAllowed>().is_some() {
         return err!(
             HttpError::Other("Method not allowed".to_string()),
@@ -0,0 +1,85 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        channel::Channel,
+        error::ResponseResult,
+        event::Event,
+        read_state::{AckError, ReadState, ReadStateAck},
+    },
+    with_lock,
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
+};
+
+pub fn routes(
+    clients: &ClientHolder,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    ack(clients.clone()).or(fetch())
+}
+
+#[post("/channels/{id}/ack")]
+pub async fn ack(
+    #[header = "Authentication"] token: String,
+    id: String,
+    #[json] ack: ReadStateAck,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<ReadState> {
+    let user = with_login!(token);
+
+    let Some(channel) = unwrap!(database().await.fetch_channel(id.clone()).await) else {
+        return not_found!("Channel");
+    };
+
+    if !channel
+        .get_users()
+        .await
+        .unwrap_or(vec![])
+        .contains(&user.id)
+    {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let acked = ReadState::ack(&user.id, &channel, ack.message_id.clone()).await;
+    let state = match unwrap!(acked) {
+        Ok(state) => state,
+        Err(AckError::UnknownMessage) => return not_found!("Message"),
+        Err(AckError::Behind) => return err!(HttpError::AckBehind, StatusCode::CONFLICT),
+    };
+
+    with_lock!(clients).dispatch_users(vec![user.id], &Event::ReadStateUpdate(state.clone()));
+
+    ok!(state)
+}
+
+/// Fetches the read state of every channel the user can see.
+#[get("/read-states")]
+pub async fn fetch(#[header = "Authentication"] token: String) -> ResponseResult<Vec<ReadState>> {
+    let user = with_login!(token);
+
+    let channels = unwrap!(database().await.fetch_user_channels(&user.id).await);
+    let visible = Channel::filter_visible(channels, &user.id).await;
+    let channels = unwrap!(visible);
+
+    let mut out = vec![];
+
+    for channel in channels {
+        out.push(unwrap!(
+            database()
+                .await
+                .fetch_read_state(&user.id, &channel.id)
+                .await
+        ));
+    }
+
+    ok!(out)
+}
@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        error::ResponseResult,
+        event::Event,
+        guild::Guild,
+        role::{GuildMember, GuildRolesResponse, Role, RoleCreate},
+        validation::{self, Field},
+    },
+    with_lock,
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    ClientHolder, HttpError,
+};
+
+pub fn routes(
+    clients: &ClientHolder,
+) -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    let create = create(clients.clone());
+
+    let fetch_all = fetch_all();
+
+    let delete = delete(clients.clone());
+
+    let members = fetch_members()
+        .or(add_member_role(clients.clone()))
+        .or(remove_member_role(clients.clone()));
+
+    create
+        .or(fetch_all)
+        .or(delete)
+        .or(members)
+        .map(Reply::into_response)
+        .boxed()
+}
+
+const MAX_ROLES: u64 = 50;
+
+#[post("/guilds/{id}/roles")]
+pub async fn create(
+    id: String,
+    #[header = "Authentication"] token: String,
+    #[json] create: RoleCreate,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<Role> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    if let Err(err) = validation::name(Field::RoleName, &create.name) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    let roles = unwrap!(database().await.fetch_guild_roles(&guild.id).await);
+
+    if roles.len() as u64 >= MAX_ROLES {
+        return err!(HttpError::TooManyRoles(MAX_ROLES), StatusCode::BAD_REQUEST);
+    }
+
+    let role = unwrap!(Role::new(&guild.id, &create.name).insert().await);
+
+    dispatch_roles(&guild, &clients).await;
+
+    ok!(role)
+}
+
+#[get("/guilds/{id}/roles")]
+pub async fn fetch_all(
+    id: String,
+    #[header = "Authentication"] token: String,
+) -> ResponseResult<GuildRolesResponse> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if !unwrap!(database().await.is_guild_member(&guild.id, &user.id).await) {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let roles = unwrap!(database().await.fetch_guild_roles(&guild.id).await);
+
+    ok!(GuildRolesResponse {
+        guild_id: guild.id,
+        roles,
+    })
+}
+
+/// Deletes a role, taking it from its members and removing its channel overwrites.
+#[delete("/guilds/{id}/roles/{role_id}")]
+pub async fn delete(
+    id: String,
+    role_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<Role> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let Some(role) = unwrap!(database().await.fetch_role(&role_id).await) else {
+        return not_found!("Role")
+    };
+
+    if role.guild_id != guild.id {
+        return not_found!("Role");
+    }
+
+    unwrap!(database().await.delete_role(&role.id).await);
+
+    dispatch_roles(&guild, &clients).await;
+
+    ok!(role)
+}
+
+#[get("/guilds/{id}/members")]
+pub async fn fetch_members(
+    id: String,
+    #[header = "Authentication"] token: String,
+) -> ResponseResult<Vec<GuildMember>> {
+    let user = with_login!(token);
+
+    let Some(members) = unwrap!(database().await.fetch_guild_members(&id).await).option() else {
+        return not_found!("Guild")
+    };
+
+    if !members.iter().any(|it| it.user.id == user.id) {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    ok!(members)
+}
+
+#[put("/guilds/{id}/members/{user_id}/roles/{role_id}")]
+pub async fn add_member_role(
+    id: String,
+    user_id: String,
+    role_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<GuildMember> {
+    set_member_role(token, id, user_id, role_id, true, clients).await
+}
+
+#[delete("/guilds/{id}/members/{user_id}/roles/{role_id}")]
+pub async fn remove_member_role(
+    id: String,
+    user_id: String,
+    role_id: String,
+    #[header = "Authentication"] token: String,
+    #[data] clients: ClientHolder,
+) -> ResponseResult<GuildMember> {
+    set_member_role(token, id, user_id, role_id, false, clients).await
+}
+
+/// Gives a role to a member or takes it away. Only for the guild owner.
+async fn set_member_role(
+    token: String,
+    id: String,
+    user_id: String,
+    role_id: String,
+    has_role: bool,
+    clients: ClientHolder,
+) -> ResponseResult<GuildMember> {
+    let user = with_login!(token);
+
+    let Some(guild) = unwrap!(database().await.fetch_guild(&id).await) else {
+        return not_found!("Guild")
+    };
+
+    if guild.owner_id != user.id {
+        return err!(HttpError::GuildAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    let Some(role) = unwrap!(database().await.fetch_role(&role_id).await) else {
+        return not_found!("Role")
+    };
+
+    if role.guild_id != guild.id {
+        return not_found!("Role");
+    }
+
+    let updated = database()
+        .await
+        .set_member_role(&role, &user_id, has_role)
+        .await;
+
+    let Some(member) = unwrap!(updated) else {
+        return not_found!("Member")
+    };
+
+    let users = unwrap!(database().await.fetch_guild_users(&guild.id).await)
+        .option()
+        .unwrap_or(vec![])
+        .into_iter()
+        .map(|it| it.id)
+        .collect();
+
+    with_lock!(clients).dispatch_users(users, &Event::GuildMemberUpdate(member.clone()));
+
+    ok!(member)
+}
+
+async fn dispatch_roles(guild: &Guild, clients: &ClientHolder) {
+    let database = database().await;
+    let Ok(roles) = database.fetch_guild_roles(&guild.id).await else {
+        return;
+    };
+    let users = database
+        .fetch_guild_users(&guild.id)
+        .await
+        .map(|it| it.option().unwrap_or(vec![]))
+        .unwrap_or(vec![]);
+
+    let resp = GuildRolesResponse {
+        guild_id: guild.id.clone(),
+        roles,
+    };
+
+    with_lock!(clients).dispatch_users(
+        users.into_iter().map(|it| it.id).collect(),
+        &Event::GuildRolesUpdate(resp),
+    );
+}
@@ -0,0 +1,147 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use chrono::{DateTime, Utc};
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        encryption::EncryptionError,
+        error::ResponseResult,
+        markdown,
+        scheduled_message::{ScheduledMessage, ScheduledMessageCreate, ScheduledMessageFetch},
+        validation,
+    },
+};
+
+use super::{
+    macros::{err, not_found, ok, unwrap, with_login},
+    HttpError,
+};
+
+pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    create().or(fetch()).or(cancel())
+}
+
+const MAX_SCHEDULED_MESSAGES: u64 = 50;
+
+#[post("/scheduled-messages")]
+pub async fn create(
+    #[header = "Authentication"] token: String,
+    #[json] create: ScheduledMessageCreate,
+) -> ResponseResult<ScheduledMessage> {
+    let user = with_login!(token);
+
+    if create.content.is_empty() {
+        return err!(HttpError::MessageContentEmpty, StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = validation::content(&create.content) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
+    if let Err(err) = markdown::parse(&create.content) {
+        return err!(HttpError::Markdown(err), StatusCode::BAD_REQUEST);
+    }
+
+    let Ok(send_at) = DateTime::parse_from_rfc3339(&create.send_at) else {
+        return err!(HttpError::InvalidSchedule, StatusCode::BAD_REQUEST);
+    };
+    let send_at = send_at.with_timezone(&Utc);
+
+    if send_at <= Utc::now() {
+        return err!(HttpError::InvalidSchedule, StatusCode::BAD_REQUEST);
+    }
+
+    let Some(channel) = unwrap!(
+        database()
+            .await
+            .fetch_channel(create.channel_id.clone())
+            .await
+    ) else {
+        return not_found!("Channel");
+    };
+
+    if !channel
+        .get_users()
+        .await
+        .unwrap_or(vec![])
+        .contains(&user.id)
+        || !channel.can_send(&user.id).await
+    {
+        return err!(HttpError::ChannelAccessDenied, StatusCode::FORBIDDEN);
+    }
+
+    // Scheduled content is stored in plain text until it is sent.
+    if channel.encrypted {
+        return err!(HttpError::Encryption(EncryptionError::Required), StatusCode::BAD_REQUEST);
+    }
+
+    let count = unwrap!(
+        database()
+            .await
+            .count_user_scheduled_messages(user.id.clone())
+            .await
+    );
+
+    if count >= MAX_SCHEDULED_MESSAGES {
+        return err!(
+            HttpError::TooManyScheduledMessages(MAX_SCHEDULED_MESSAGES),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    let message = unwrap!(
+        ScheduledMessage::new(
+            channel.id.clone(),
+            user.id.clone(),
+            create.content.clone(),
+            send_at,
+            create.repeat,
+        )
+        .insert()
+        .await
+    );
+
+    ok!(message)
+}
+
+#[get("/scheduled-messages")]
+pub async fn fetch(
+    #[header = "Authentication"] token: String,
+    #[filter = "warp::query"] query: ScheduledMessageFetch,
+) -> ResponseResult<Vec<ScheduledMessage>> {
+    let user = with_login!(token);
+
+    ok!(unwrap!(
+        database()
+            .await
+            .fetch_user_scheduled_messages(user.id.clone(), query.channel.clone())
+            .await
+    ))
+}
+
+#[delete("/scheduled-messages/{id}")]
+pub async fn cancel(
+    #[header = "Authentication"] token: String,
+    id: String,
+) -> ResponseResult<ScheduledMessage> {
+    let user = with_login!(token);
+
+    let Some(message) = unwrap!(database().await.fetch_scheduled_message(id.clone()).await) else {
+        return not_found!("Scheduled message");
+    };
+
+    if message.author_id != user.id {
+        return not_found!("Scheduled message");
+    }
+
+    if !unwrap!(database().await.delete_scheduled_message(id.clone()).await) {
+        return not_found!("Scheduled message");
+    }
+
+    ok!(message)
+}
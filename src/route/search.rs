@@ -0,0 +1,79 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::*;
+use warp::{hyper::StatusCode, Filter, Rejection, Reply};
+
+use crate::{
+    database,
+    structures::{
+        channel::{Channel, ChannelLocation},
+        error::ResponseResult,
+        message::{MessageResponse, MessageSearch},
+    },
+};
+
+use super::{
+    macros::{err, ok, unwrap, with_login},
+    HttpError,
+};
+
+pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone {
+    messages()
+}
+
+#[get("/search/messages")]
+pub async fn messages(
+    #[header = "Authentication"] token: String,
+    #[filter = "warp::query"] search: MessageSearch,
+) -> ResponseResult<Vec<MessageResponse>> {
+    let user = with_login!(token);
+
+    if search.query.trim().is_empty() {
+        return err!(HttpError::SearchQueryEmpty, StatusCode::BAD_REQUEST);
+    }
+
+    let max = search.max.unwrap_or(25).clamp(1, 25);
+
+    let channels = unwrap!(database().await.fetch_user_channels(&user.id).await);
+
+    let channels: Vec<_> = channels
+        .into_iter()
+        .filter(|channel| search.channel.as_ref().is_none_or(|it| *it == channel.id))
+        .filter(|channel| match &search.guild {
+            Some(guild) => {
+                matches!(&channel.location, ChannelLocation::Guild { guild: it } if it == guild)
+            }
+            None => true,
+        })
+        .collect();
+
+    // Permissions are resolved once per guild, and only visible channels are searched.
+    let visible = Channel::filter_visible(channels, &user.id).await;
+    let accessible = unwrap!(visible);
+
+    if accessible.is_empty() {
+        return ok!(vec![]);
+    }
+
+    let ids: Vec<_> = accessible.iter().map(|it| it.id.clone()).collect();
+
+    let messages = unwrap!(
+        database()
+            .await
+            .search_messages(&search, ids.clone(), max)
+            .await
+    );
+
+    let mut out = vec![];
+
+    for message in messages {
+        let Some(channel) = accessible.iter().find(|it| it.id == message.channel_id) else {
+            continue;
+        };
+        out.push(MessageResponse::from_message(message, channel.clone()).await);
+    }
+
+    ok!(out)
+}
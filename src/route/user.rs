@@ -11,6 +11,7 @@ use crate::{
         auth::Login,
         error::ResponseResult,
         user::{User, UserCreateRequest, UserLoginRequest, UserLoginResponse},
+        validation::{self, Field},
     },
 };
 
@@ -27,6 +28,10 @@ pub fn routes() -> impl Filter<Extract = impl Reply, Error = Rejection> + Clone
 pub async fn create(
     #[json] user: UserCreateRequest
 ) -> ResponseResult<UserLoginResponse> {
+    if let Err(err) = validation::name(Field::Username, &user.username) {
+        return err!(HttpError::Validation(err), StatusCode::BAD_REQUEST);
+    }
+
     let service = user.service;
 
     let token = match service.fetch_token(&user.oauth_code).await {
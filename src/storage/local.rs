@@ -0,0 +1,56 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{io::ErrorKind, path::PathBuf};
+
+use super::{BlobStorage, StorageResult};
+
+/// Stores blobs as files under `STORAGE_PATH`, `./attachments` by default.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn from_env() -> Self {
+        Self {
+            root: std::env::var("STORAGE_PATH")
+                .unwrap_or("./attachments".to_string())
+                .into(),
+        }
+    }
+}
+
+impl BlobStorage for LocalStorage {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        _content_type: &'a str,
+    ) -> StorageResult<'a, ()> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.root).await?;
+            tokio::fs::write(self.root.join(key), data).await?;
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> StorageResult<'a, Option<Vec<u8>>> {
+        Box::pin(async move {
+            match tokio::fs::read(self.root.join(key)).await {
+                Ok(data) => Ok(Some(data)),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                Err(err) => Err(err.into()),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageResult<'a, ()> {
+        Box::pin(async move {
+            match tokio::fs::remove_file(self.root.join(key)).await {
+                Err(err) if err.kind() != ErrorKind::NotFound => Err(err.into()),
+                _ => Ok(()),
+            }
+        })
+    }
+}
@@ -0,0 +1,71 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+mod local;
+mod s3;
+
+use std::{fmt::Display, future::Future, pin::Pin};
+
+use rweb::Schema;
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+pub type StorageResult<'a, T> = Pin<Box<dyn Future<Output = Result<T, StorageError>> + Send + 'a>>;
+
+/// Somewhere to keep uploaded files.
+///
+/// Chosen with the `STORAGE` environment variable, either `local` (the default) or `s3`.
+pub trait BlobStorage: Send + Sync {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        content_type: &'a str,
+    ) -> StorageResult<'a, ()>;
+
+    /// Returns `None` if there is no blob with this key.
+    fn get<'a>(&'a self, key: &'a str) -> StorageResult<'a, Option<Vec<u8>>>;
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageResult<'a, ()>;
+}
+
+#[derive(Debug, Schema)]
+pub enum StorageError {
+    Io(String),
+    Http(String),
+    Status(u16),
+}
+
+/// # Panics
+/// - if `STORAGE` is set to an unknown backend
+/// - if the `s3` backend is missing configuration
+pub fn create() -> Box<dyn BlobStorage> {
+    match std::env::var("STORAGE").as_deref() {
+        Ok("s3") => Box::new(S3Storage::from_env()),
+        Ok("local") | Err(_) => Box::new(LocalStorage::from_env()),
+        Ok(other) => panic!("Unknown STORAGE backend: {other}"),
+    }
+}
+
+impl From<std::io::Error> for StorageError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(format!("{:?}", value))
+    }
+}
+
+impl From<reqwest::Error> for StorageError {
+    fn from(value: reqwest::Error) -> Self {
+        Self::Http(format!("{:?}", value))
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(err) | Self::Http(err) => f.write_str(err),
+            Self::Status(status) => write!(f, "Storage responded with status {status}"),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use super::{BlobStorage, StorageError, StorageResult};
+
+/// Stores blobs in an S3 compatible bucket, such as MinIO.
+///
+/// Configured with `S3_ENDPOINT`, `S3_BUCKET`, `S3_ACCESS_KEY`, `S3_SECRET_KEY`
+/// and optionally `S3_REGION`. Objects are addressed path-style.
+pub struct S3Storage {
+    client: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl S3Storage {
+    /// # Panics
+    /// - if any required variable is missing or `S3_ENDPOINT` is not a URL
+    pub fn from_env() -> Self {
+        let var = |name: &str| {
+            std::env::var(name).unwrap_or_else(|_| panic!("No {name} found in environment!"))
+        };
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: Url::parse(&var("S3_ENDPOINT")).expect("S3_ENDPOINT is not a valid URL"),
+            bucket: var("S3_BUCKET"),
+            region: std::env::var("S3_REGION").unwrap_or("us-east-1".to_string()),
+            access_key: var("S3_ACCESS_KEY"),
+            secret_key: var("S3_SECRET_KEY"),
+        }
+    }
+
+    fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).unwrap();
+        mac.update(data.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Sends a request signed with AWS signature version 4.
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>,
+    ) -> Result<reqwest::Response, StorageError> {
+        let path = format!("/{}/{}", self.bucket, key);
+        let url = self
+            .endpoint
+            .join(&path)
+            .map_err(|it| StorageError::Http(it.to_string()))?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let now = Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+
+        let canonical_request = format!(
+            "{method}\n{}\n\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\n\n{signed_headers}\n{payload_hash}",
+            url.path()
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = ["s3", "aws4_request"].iter().fold(
+            Self::hmac(
+                &Self::hmac(format!("AWS4{}", self.secret_key).as_bytes(), &date),
+                &self.region,
+            ),
+            |key, part| Self::hmac(&key, part),
+        );
+        let signature = hex::encode(Self::hmac(&signing_key, &string_to_sign));
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("x-amz-date", timestamp)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(
+                "Authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+                    self.access_key
+                ),
+            );
+        if let Some(content_type) = content_type {
+            request = request.header("Content-Type", content_type);
+        }
+
+        Ok(request.body(body).send().await?)
+    }
+}
+
+impl BlobStorage for S3Storage {
+    fn put<'a>(
+        &'a self,
+        key: &'a str,
+        data: Vec<u8>,
+        content_type: &'a str,
+    ) -> StorageResult<'a, ()> {
+        Box::pin(async move {
+            let response = self
+                .send(Method::PUT, key, data, Some(content_type))
+                .await?;
+            if !response.status().is_success() {
+                return Err(StorageError::Status(response.status().as_u16()));
+            }
+            Ok(())
+        })
+    }
+
+    fn get<'a>(&'a self, key: &'a str) -> StorageResult<'a, Option<Vec<u8>>> {
+        Box::pin(async move {
+            let response = self.send(Method::GET, key, vec![], None).await?;
+            match response.status() {
+                StatusCode::NOT_FOUND => Ok(None),
+                status if status.is_success() => Ok(Some(response.bytes().await?.to_vec())),
+                status => Err(StorageError::Status(status.as_u16())),
+            }
+        })
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> StorageResult<'a, ()> {
+        Box::pin(async move {
+            let response = self.send(Method::DELETE, key, vec![], None).await?;
+            if !response.status().is_success() && response.status() != StatusCode::NOT_FOUND {
+                return Err(StorageError::Status(response.status().as_u16()));
+            }
+            Ok(())
+        })
+    }
+}
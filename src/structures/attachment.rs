@@ -0,0 +1,132 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{database, generate_ulid, storage};
+
+/// Metadata for an uploaded file. The file itself lives in blob storage under `id`.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Attachment {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    pub size: u64,
+    pub uploader_id: String,
+}
+
+/// An attachment along with its contents, for downloading.
+#[derive(Debug, Serialize, Schema)]
+pub struct AttachmentFile {
+    pub attachment: Attachment,
+    pub data: Vec<u8>,
+}
+
+const DEFAULT_MAX_SIZE: u64 = 8 * 1024 * 1024;
+const MAX_FILENAME_LENGTH: usize = 128;
+
+/// Types browsers may display inline. Anything else, such as SVG or HTML, is downloaded.
+const INLINE_TYPES: [&str; 13] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "image/avif",
+    "video/mp4",
+    "video/webm",
+    "video/ogg",
+    "audio/mpeg",
+    "audio/ogg",
+    "audio/wav",
+    "audio/webm",
+    "audio/flac",
+];
+
+impl Attachment {
+    pub fn new(filename: &str, content_type: &str, size: u64, uploader_id: &str) -> Self {
+        Self {
+            id: generate_ulid(),
+            filename: Self::sanitize_filename(filename),
+            content_type: content_type.to_string(),
+            size,
+            uploader_id: uploader_id.to_string(),
+        }
+    }
+
+    /// The largest accepted upload in bytes, set with `MAX_ATTACHMENT_SIZE`.
+    pub fn max_size() -> u64 {
+        std::env::var("MAX_ATTACHMENT_SIZE")
+            .ok()
+            .and_then(|it| it.parse().ok())
+            .unwrap_or(DEFAULT_MAX_SIZE)
+    }
+
+    fn sanitize_filename(filename: &str) -> String {
+        let name: String = filename
+            .rsplit(['/', '\\'])
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|it| !it.is_control() && *it != '"')
+            .take(MAX_FILENAME_LENGTH)
+            .collect();
+        if name.is_empty() {
+            return "file".to_string();
+        }
+        name
+    }
+
+    /// Whether browsers may display the file inline rather than download it.
+    ///
+    /// The content type is whatever the uploader sent, so only its essence is compared.
+    pub fn is_inline(&self) -> bool {
+        let essence = self
+            .content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        INLINE_TYPES.contains(&essence.as_str())
+    }
+
+    pub async fn insert(self) -> Result<Self, Error> {
+        database().await.create_attachment(self).await
+    }
+
+    pub async fn remove_all(attachments: &[Attachment]) {
+        for attachment in attachments {
+            let _ = storage().delete(&attachment.id).await;
+        }
+        let ids = attachments.iter().map(|it| it.id.clone()).collect();
+        let _ = database().await.delete_attachments(ids).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(content_type: &str) -> Attachment {
+        Attachment::new("file", content_type, 0, "user")
+    }
+
+    #[test]
+    fn media_is_inline() {
+        assert!(attachment("image/png").is_inline());
+        assert!(attachment("IMAGE/PNG").is_inline());
+        assert!(attachment("video/mp4; codecs=avc1").is_inline());
+    }
+
+    #[test]
+    fn scriptable_types_are_downloaded() {
+        assert!(!attachment("image/svg+xml").is_inline());
+        assert!(!attachment("image/SVG+xml").is_inline());
+        assert!(!attachment("image/svg+xml; charset=utf-8").is_inline());
+        assert!(!attachment("text/html").is_inline());
+        assert!(!attachment("image/png-but-not-really").is_inline());
+    }
+}
@@ -2,6 +2,9 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
 use mongodb::error::Error;
 use rweb::Schema;
 use serde::{
@@ -10,27 +13,124 @@ use serde::{
     Deserialize, Serialize,
 };
 
-use crate::{database, database::DatabaseGuildResponse, generate_ulid};
+use crate::{database, database::DatabaseGuildResponse, generate_ulid, with_lock};
+
+use super::{
+    attachment::Attachment,
+    client::ClientHolder,
+    event::Event,
+    restricted_string::RestrictedString,
+    role::{GuildPermissions, Permission, PermissionOverwrite},
+    validation::Field,
+};
 
-use super::restricted_string::RestrictedString;
+/// Messages of deleted channels are deleted this many at a time.
+const DELETE_BATCH_SIZE: i64 = 500;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Schema)]
 pub struct Channel {
     pub id: String,
     pub name: String,
     pub location: ChannelLocation,
+    /// Only set for threads.
+    pub thread: Option<ThreadMetadata>,
+    /// Messages in encrypted DMs only carry ciphertext the server cannot read.
+    #[serde(default)]
+    pub encrypted: bool,
+    pub topic: Option<String>,
+    /// Orders channels among their siblings, ties are broken by id.
+    #[serde(default)]
+    pub position: u32,
+    #[serde(default)]
+    pub kind: ChannelKind,
+    /// The category of a guild channel.
+    pub category: Option<String>,
+    /// Only for guild channels, threads follow those of their parent.
+    #[serde(default)]
+    pub overwrites: Vec<PermissionOverwrite>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum ChannelKind {
+    #[default]
+    #[serde(rename = "text")]
+    Text,
+    /// Only the guild owner may send messages.
+    #[serde(rename = "announcement")]
+    Announcement,
+    /// Groups guild channels, and holds no messages itself.
+    #[serde(rename = "category")]
+    Category,
 }
 
 #[derive(Debug, Clone, Schema)]
 pub enum ChannelLocation {
     Dm { members: Vec<String> },
     Guild { guild: String },
+    /// A thread started from the `origin` message in the `parent` guild channel.
+    ///
+    /// The thread outlives its origin, so the message may have been deleted since.
+    Thread { parent: String, origin: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct ThreadMetadata {
+    pub creator_id: String,
+    pub archived: bool,
+    /// Minutes of inactivity after which the thread is archived.
+    pub auto_archive_after: u64,
+    pub archive_at: String,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct ThreadCreate {
+    pub message_id: String,
+    pub name: String,
+    pub auto_archive_after: Option<u64>,
 }
 
 #[derive(Debug, Deserialize, Schema)]
 pub struct ChannelCreate {
     pub name: String,
     pub location: ChannelLocation,
+    /// Only DMs may be encrypted, and only when they are created.
+    #[serde(default)]
+    pub encrypted: bool,
+    /// Announcement channels and categories must be in a guild.
+    #[serde(default)]
+    pub kind: ChannelKind,
+    pub category: Option<String>,
+}
+
+/// Changes to a channel. Fields that are not set are kept.
+#[derive(Debug, Deserialize, Schema)]
+pub struct ChannelUpdate {
+    pub name: Option<String>,
+    /// An empty topic removes it.
+    pub topic: Option<String>,
+    pub position: Option<u32>,
+}
+
+/// Where a guild channel goes when reordering. Channels without a category are top level.
+#[derive(Debug, Deserialize, Schema)]
+pub struct ChannelPosition {
+    pub id: String,
+    pub position: u32,
+    pub category: Option<String>,
+}
+
+/// The channels of a guild in order, with the channels of each category under it.
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct GuildChannelsResponse {
+    pub guild_id: String,
+    pub channels: Vec<ChannelNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct ChannelNode {
+    pub channel: Channel,
+    /// Only categories have children.
+    pub children: Vec<Channel>,
 }
 
 #[derive(Debug, Clone, Serialize, Schema)]
@@ -38,12 +138,56 @@ pub struct ChannelResponse {
     pub channel: Channel,
 }
 
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct ChannelPinsResponse {
+    pub channel_id: String,
+    pub message_id: String,
+    /// `None` if the message was unpinned.
+    pub pinned_at: Option<String>,
+}
+
 impl Channel {
     pub fn new(name: &str, location: ChannelLocation) -> Self {
         Self {
             id: generate_ulid(),
-            name: RestrictedString::no_space(name),
+            name: RestrictedString::no_space(name, Field::ChannelName),
             location,
+            thread: None,
+            encrypted: false,
+            topic: None,
+            position: 0,
+            kind: ChannelKind::Text,
+            category: None,
+            overwrites: vec![],
+        }
+    }
+
+    pub fn with_kind(mut self, kind: ChannelKind, category: Option<String>) -> Self {
+        self.kind = kind;
+        self.category = category;
+        self
+    }
+
+    pub fn with_encryption(mut self, encrypted: bool) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    pub fn new_thread(
+        name: &str,
+        parent: String,
+        origin: String,
+        creator_id: String,
+        auto_archive_after: u64,
+    ) -> Self {
+        Self {
+            thread: Some(ThreadMetadata {
+                creator_id,
+                archived: false,
+                auto_archive_after,
+                archive_at: ThreadMetadata::archive_at(auto_archive_after),
+            }),
+            ..Self::new(name, ChannelLocation::Thread { parent, origin })
         }
     }
 
@@ -51,18 +195,256 @@ impl Channel {
         database().await.create_channel(self).await
     }
 
+    /// Archives the threads started from deleted messages and sends a `ThreadUpdate` for each.
+    pub async fn archive_orphaned_threads(message_ids: Vec<String>, clients: &ClientHolder) {
+        let Ok(threads) = database().await.archive_threads_of(message_ids).await else {
+            return;
+        };
+
+        for thread in threads {
+            let users = thread.get_users().await.unwrap_or(vec![]);
+            with_lock!(clients).dispatch_users(
+                users,
+                &Event::ThreadUpdate(ChannelResponse::from_channel(thread)),
+            );
+        }
+    }
+
+    /// Deletes the channel and its threads, with their messages and everything attached to them.
+    ///
+    /// Returns the deleted channels, threads first.
+    pub async fn delete(self) -> Result<Vec<Self>, Error> {
+        let database = database().await;
+
+        let mut channels = database.fetch_threads(&self.id).await?;
+        channels.push(self);
+
+        for channel in &channels {
+            // The channel goes first, so no messages can be sent to it while it is emptied.
+            database.delete_channel(&channel.id).await?;
+
+            loop {
+                let messages = database
+                    .fetch_message_batch(&channel.id, DELETE_BATCH_SIZE)
+                    .await?;
+                if messages.is_empty() {
+                    break;
+                }
+                let ids = messages.iter().map(|it| it.id.clone()).collect();
+                database.delete_messages(channel.id.clone(), ids).await?;
+                for message in messages {
+                    Attachment::remove_all(&message.attachments).await;
+                }
+            }
+
+            if channel.kind == ChannelKind::Category {
+                database.clear_category(&channel.id).await?;
+            }
+            database.delete_channel_read_states(&channel.id).await?;
+            database
+                .delete_channel_scheduled_messages(&channel.id)
+                .await?;
+        }
+
+        Ok(channels)
+    }
+
+    /// The users that can see the channel: the members of a DM, or the guild members
+    /// whose overwrites let them view it.
     pub async fn get_users(&self) -> DatabaseGuildResponse<Vec<String>> {
-        DatabaseGuildResponse::Ok(match &self.location {
-            ChannelLocation::Dm { members } => members.clone(),
-            ChannelLocation::Guild { guild } => database()
-                .await
-                .fetch_guild_users(guild)
+        if let ChannelLocation::Dm { members } = &self.location {
+            return DatabaseGuildResponse::Ok(members.clone());
+        }
+
+        let Some((guild, overwrites)) = self.get_guild_overwrites().await else {
+            return DatabaseGuildResponse::NoGuild
+        };
+        let Ok(Some(permissions)) = GuildPermissions::fetch(&guild).await else {
+            return DatabaseGuildResponse::NoGuild
+        };
+
+        DatabaseGuildResponse::Ok(permissions.viewers(&overwrites))
+    }
+
+    /// The permissions of a user in the channel. DM members and the guild owner have them all.
+    pub async fn get_permissions(&self, user_id: &str) -> Vec<Permission> {
+        if let ChannelLocation::Dm { members } = &self.location {
+            if members.iter().any(|it| it == user_id) {
+                return Permission::ALL.to_vec();
+            }
+            return vec![];
+        }
+
+        let Some((guild, overwrites)) = self.get_guild_overwrites().await else {
+            return vec![]
+        };
+        let Ok(Some(permissions)) = GuildPermissions::fetch_member(&guild, user_id).await else {
+            return vec![]
+        };
+
+        permissions.resolve(&overwrites, user_id)
+    }
+
+    /// Whether a user can see the channel, without fetching every member of its guild.
+    pub async fn can_view(&self, user_id: &str) -> bool {
+        self.get_permissions(user_id)
+            .await
+            .contains(&Permission::ViewChannel)
+    }
+
+    /// The guild of a guild channel or thread, with the overwrites that apply in it,
+    /// those of the parent for threads.
+    pub async fn get_guild_overwrites(&self) -> Option<(String, Vec<PermissionOverwrite>)> {
+        match &self.location {
+            ChannelLocation::Dm { .. } => None,
+            ChannelLocation::Guild { guild } => Some((guild.clone(), self.overwrites.clone())),
+            ChannelLocation::Thread { parent, .. } => {
+                let parent = database().await.fetch_channel(parent.clone()).await;
+                match parent.ok()? {
+                    Some(Channel {
+                        location: ChannelLocation::Guild { guild },
+                        overwrites,
+                        ..
+                    }) => Some((guild, overwrites)),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    /// Keeps the channels a user can see, fetching each guild and the user's roles in it once.
+    ///
+    /// The parents of threads are looked up in `channels` before they are fetched.
+    pub async fn filter_visible(
+        mut channels: Vec<Channel>,
+        user_id: &str,
+    ) -> Result<Vec<Channel>, Error> {
+        let mut guilds: HashMap<String, Option<GuildPermissions>> = HashMap::new();
+        let mut visible = vec![];
+
+        for channel in &channels {
+            let (guild, overwrites) = match &channel.location {
+                ChannelLocation::Dm { members } => {
+                    visible.push(members.iter().any(|it| it == user_id));
+                    continue;
+                }
+                ChannelLocation::Guild { guild } => (guild.clone(), channel.overwrites.clone()),
+                ChannelLocation::Thread { parent, .. } => {
+                    let parent = match channels.iter().find(|it| it.id == *parent) {
+                        Some(parent) => Some(parent.clone()),
+                        None => database().await.fetch_channel(parent.clone()).await?,
+                    };
+                    let Some(Channel {
+                        location: ChannelLocation::Guild { guild },
+                        overwrites,
+                        ..
+                    }) = parent
+                    else {
+                        visible.push(false);
+                        continue;
+                    };
+                    (guild, overwrites)
+                }
+            };
+
+            if !guilds.contains_key(&guild) {
+                let permissions = GuildPermissions::fetch_member(&guild, user_id).await?;
+                guilds.insert(guild.clone(), permissions);
+            }
+
+            visible.push(guilds[&guild].as_ref().is_some_and(|it| {
+                it.resolve(&overwrites, user_id)
+                    .contains(&Permission::ViewChannel)
+            }));
+        }
+
+        let mut visible = visible.into_iter();
+        channels.retain(|_| visible.next().unwrap_or(false));
+        Ok(channels)
+    }
+
+    /// Whether a member may send messages in the channel.
+    ///
+    /// Categories hold no messages, only the guild owner may post announcements,
+    /// and overwrites may deny sending in text channels.
+    pub async fn can_send(&self, user_id: &str) -> bool {
+        match self.kind {
+            ChannelKind::Text => self
+                .get_permissions(user_id)
                 .await
-                .unwrap_or(DatabaseGuildResponse::NoGuild)?
-                .iter()
-                .map(|it| it.id.clone())
-                .collect(),
-        })
+                .contains(&Permission::SendMessages),
+            ChannelKind::Announcement => self.get_owner().await.as_deref() == Some(user_id),
+            ChannelKind::Category => false,
+        }
+    }
+
+    pub async fn get_owner(&self) -> Option<String> {
+        database()
+            .await
+            .fetch_guild(&self.get_guild().await.option()?)
+            .await
+            .unwrap_or(None)
+            .map(|it| it.owner_id)
+    }
+
+    /// The guild of the channel, or of the parent channel for threads.
+    pub async fn get_guild(&self) -> DatabaseGuildResponse<String> {
+        match &self.location {
+            ChannelLocation::Dm { .. } => DatabaseGuildResponse::NoGuild,
+            ChannelLocation::Guild { guild } => DatabaseGuildResponse::Ok(guild.clone()),
+            ChannelLocation::Thread { parent, .. } => {
+                let parent = database().await.fetch_channel(parent.clone()).await;
+                match parent.unwrap_or(None).map(|it| it.location) {
+                    Some(ChannelLocation::Guild { guild }) => DatabaseGuildResponse::Ok(guild),
+                    _ => DatabaseGuildResponse::NoGuild,
+                }
+            }
+        }
+    }
+}
+
+impl GuildChannelsResponse {
+    /// Orders the channels of a guild into a tree. Threads are left out.
+    ///
+    /// Channels whose category no longer exists are put at the top level.
+    pub fn new(guild_id: String, mut channels: Vec<Channel>) -> Self {
+        channels.retain(|it| matches!(it.location, ChannelLocation::Guild { .. }));
+        channels.sort_by(|a, b| (a.position, &a.id).cmp(&(b.position, &b.id)));
+
+        let categories: Vec<String> = channels
+            .iter()
+            .filter(|it| it.kind == ChannelKind::Category)
+            .map(|it| it.id.clone())
+            .collect();
+
+        let (children, top): (Vec<_>, Vec<_>) = channels.into_iter().partition(|it| {
+            it.kind != ChannelKind::Category
+                && it.category.as_ref().is_some_and(|it| categories.contains(it))
+        });
+
+        let channels = top
+            .into_iter()
+            .map(|channel| ChannelNode {
+                children: children
+                    .iter()
+                    .filter(|it| it.category.as_ref() == Some(&channel.id))
+                    .cloned()
+                    .collect(),
+                channel,
+            })
+            .collect();
+
+        Self { guild_id, channels }
+    }
+}
+
+/// The allowed values of `auto_archive_after`, in minutes.
+pub const AUTO_ARCHIVE_DURATIONS: [u64; 4] = [60, 24 * 60, 3 * 24 * 60, 7 * 24 * 60];
+
+impl ThreadMetadata {
+    /// When a thread that is active now should be archived.
+    pub fn archive_at(auto_archive_after: u64) -> String {
+        (Utc::now() + Duration::minutes(auto_archive_after as i64)).to_rfc3339()
     }
 }
 
@@ -84,6 +466,13 @@ impl Serialize for ChannelLocation {
                 ser.serialize_field("id", guild)?;
                 ser.end()
             }
+            Self::Thread { parent, origin } => {
+                let mut ser = serializer.serialize_struct("ChannelLocation", 3)?;
+                ser.serialize_field("type", "thread")?;
+                ser.serialize_field("parent", parent)?;
+                ser.serialize_field("origin", origin)?;
+                ser.end()
+            }
         }
     }
 }
@@ -113,11 +502,15 @@ impl<'de> Visitor<'de> for LocationVisitor {
         let mut name: Option<&str> = None;
         let mut members: Vec<String> = vec![];
         let mut id: Option<String> = None;
+        let mut parent: Option<String> = None;
+        let mut origin: Option<String> = None;
         while let Some(key) = map.next_key::<&str>()? {
             match key {
                 "type" => name = Some(map.next_value()?),
                 "members" => members = map.next_value()?,
                 "id" => id = Some(map.next_value()?),
+                "parent" => parent = Some(map.next_value()?),
+                "origin" => origin = Some(map.next_value()?),
                 _ => {}
             }
         }
@@ -133,7 +526,16 @@ impl<'de> Visitor<'de> for LocationVisitor {
                     Err(de::Error::missing_field("id"))
                 }
             }
-            _ => Err(de::Error::unknown_variant("type", &["dm", "guild"])),
+            "thread" => {
+                let Some(parent) = parent else {
+                    return Err(de::Error::missing_field("parent"))
+                };
+                let Some(origin) = origin else {
+                    return Err(de::Error::missing_field("origin"))
+                };
+                Ok(ChannelLocation::Thread { parent, origin })
+            }
+            _ => Err(de::Error::unknown_variant("type", &["dm", "guild", "thread"])),
         }
     }
 }
@@ -143,3 +545,75 @@ impl ChannelResponse {
         Self { channel }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD: &str = "guild";
+
+    fn channel(id: &str, position: u32, kind: ChannelKind, category: Option<&str>) -> Channel {
+        let location = ChannelLocation::Guild {
+            guild: GUILD.to_string(),
+        };
+        let mut channel = Channel::new(id, location).with_kind(kind, category.map(str::to_string));
+        channel.id = id.to_string();
+        channel.position = position;
+        channel
+    }
+
+    fn ids(response: &GuildChannelsResponse) -> Vec<(&str, Vec<&str>)> {
+        response
+            .channels
+            .iter()
+            .map(|node| {
+                let children = node.children.iter().map(|it| it.id.as_str()).collect();
+                (node.channel.id.as_str(), children)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn channels_are_ordered_by_position_then_id() {
+        let channels = vec![
+            channel("c", 1, ChannelKind::Text, None),
+            channel("b", 1, ChannelKind::Announcement, None),
+            channel("a", 2, ChannelKind::Text, None),
+            Channel::new_thread(
+                "thread",
+                "a".to_string(),
+                "m".to_string(),
+                "u".to_string(),
+                60,
+            ),
+        ];
+        let response = GuildChannelsResponse::new(GUILD.to_string(), channels);
+        assert_eq!(
+            ids(&response),
+            [("b", vec![]), ("c", vec![]), ("a", vec![])]
+        );
+    }
+
+    #[test]
+    fn channels_are_nested_under_their_category() {
+        let channels = vec![
+            channel("text", 0, ChannelKind::Text, Some("first")),
+            channel("second", 2, ChannelKind::Category, None),
+            channel("first", 1, ChannelKind::Category, None),
+            channel("orphan", 3, ChannelKind::Text, Some("deleted")),
+            channel("nested", 0, ChannelKind::Category, Some("first")),
+            channel("other", 1, ChannelKind::Text, Some("second")),
+            channel("top", 0, ChannelKind::Text, Some("first")),
+        ];
+        let response = GuildChannelsResponse::new(GUILD.to_string(), channels);
+        assert_eq!(
+            ids(&response),
+            [
+                ("nested", vec![]),
+                ("first", vec!["text", "top"]),
+                ("second", vec!["other"]),
+                ("orphan", vec![]),
+            ]
+        );
+    }
+}
@@ -76,6 +76,16 @@ impl Clients {
         }
     }
 
+    /// Dispatches to a user's clients other than `client`.
+    pub fn dispatch_user_except(&self, user: &str, client: &Client, event: &Event) {
+        if let Some(clients) = self.get(user) {
+            clients
+                .iter()
+                .filter(|it| it.id != client.id)
+                .for_each(|it| it.send(event));
+        }
+    }
+
     fn dispatch_to(clients: &[Client], event: &Event) {
         clients.iter().for_each(|client| client.send(event));
     }
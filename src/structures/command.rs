@@ -0,0 +1,236 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+
+use mongodb::error::Error;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{database, database::DatabaseGuildResponse, generate_ulid};
+
+use super::{interaction::InteractionOption, validation};
+
+static NAME: Lazy<Regex> = Lazy::new(|| Regex::new("^[a-z0-9_-]{1,32}$").unwrap());
+
+const MAX_DESCRIPTION_LENGTH: usize = 100;
+const MAX_OPTIONS: usize = 25;
+const MAX_STRING_LENGTH: usize = 6000;
+
+/// A slash command a bot registered in a guild.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Command {
+    pub id: String,
+    pub guild_id: String,
+    pub bot_id: String,
+    pub name: String,
+    pub description: String,
+    pub options: Vec<CommandOption>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct CommandOption {
+    pub name: String,
+    pub description: String,
+    pub kind: OptionKind,
+    #[serde(default)]
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum OptionKind {
+    #[serde(rename = "string")]
+    String,
+    #[serde(rename = "integer")]
+    Integer,
+    #[serde(rename = "number")]
+    Number,
+    #[serde(rename = "boolean")]
+    Boolean,
+    /// The id of a member of the guild.
+    #[serde(rename = "user")]
+    User,
+    /// The id of a channel in the guild.
+    #[serde(rename = "channel")]
+    Channel,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct CommandCreate {
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub options: Vec<CommandOption>,
+}
+
+#[derive(Debug, Schema)]
+pub enum CommandError {
+    InvalidName { name: String },
+    InvalidDescription,
+    TooManyOptions,
+    DuplicateOption { name: String },
+    RequiredAfterOptional { name: String },
+    UnknownOption { name: String },
+    MissingOption { name: String },
+    InvalidOptionValue { name: String, kind: OptionKind },
+}
+
+impl CommandCreate {
+    /// Checks the command and its options, returning the command for the bot.
+    pub fn validate(self, guild_id: &str, bot_id: &str) -> Result<Command, CommandError> {
+        check_name(&self.name)?;
+        check_description(&self.description)?;
+
+        if self.options.len() > MAX_OPTIONS {
+            return Err(CommandError::TooManyOptions);
+        }
+
+        let mut optional = false;
+        for (i, option) in self.options.iter().enumerate() {
+            check_name(&option.name)?;
+            check_description(&option.description)?;
+            if self.options[..i].iter().any(|it| it.name == option.name) {
+                let name = option.name.clone();
+                return Err(CommandError::DuplicateOption { name });
+            }
+            if option.required && optional {
+                let name = option.name.clone();
+                return Err(CommandError::RequiredAfterOptional { name });
+            }
+            optional |= !option.required;
+        }
+
+        Ok(Command {
+            id: generate_ulid(),
+            guild_id: guild_id.to_string(),
+            bot_id: bot_id.to_string(),
+            name: self.name,
+            description: self.description,
+            options: self.options,
+        })
+    }
+}
+
+impl Command {
+    /// Checks the options of an invocation against the registered options.
+    ///
+    /// User and channel options must refer to members and channels of the command's guild.
+    pub async fn validate_options(
+        &self,
+        options: &[InteractionOption],
+    ) -> Result<Result<(), CommandError>, Error> {
+        for (i, option) in options.iter().enumerate() {
+            let name = option.name.clone();
+            if options[..i].iter().any(|it| it.name == option.name) {
+                return Ok(Err(CommandError::DuplicateOption { name }));
+            }
+            let Some(registered) = self.options.iter().find(|it| it.name == option.name) else {
+                return Ok(Err(CommandError::UnknownOption { name }));
+            };
+            if !registered
+                .kind
+                .accepts(&option.value, &self.guild_id)
+                .await?
+            {
+                let kind = registered.kind;
+                return Ok(Err(CommandError::InvalidOptionValue { name, kind }));
+            }
+        }
+
+        for registered in self.options.iter().filter(|it| it.required) {
+            if !options.iter().any(|it| it.name == registered.name) {
+                let name = registered.name.clone();
+                return Ok(Err(CommandError::MissingOption { name }));
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+impl OptionKind {
+    async fn accepts(self, value: &Value, guild_id: &str) -> Result<bool, Error> {
+        Ok(match self {
+            Self::String => value
+                .as_str()
+                .is_some_and(|it| !validation::exceeds(it, MAX_STRING_LENGTH)),
+            Self::Integer => value.is_i64() || value.is_u64(),
+            Self::Number => value.is_number(),
+            Self::Boolean => value.is_boolean(),
+            Self::User => {
+                let Some(id) = value.as_str() else {
+                    return Ok(false);
+                };
+                let users = database().await.fetch_guild_users(guild_id).await?;
+                users
+                    .option()
+                    .is_some_and(|it| it.iter().any(|user| user.id == id))
+            }
+            Self::Channel => {
+                let Some(id) = value.as_str() else {
+                    return Ok(false);
+                };
+                let Some(channel) = database().await.fetch_channel(id.to_string()).await? else {
+                    return Ok(false);
+                };
+                matches!(channel.get_guild().await, DatabaseGuildResponse::Ok(guild) if guild == guild_id)
+            }
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::User => "member of the guild",
+            Self::Channel => "channel of the guild",
+        }
+    }
+}
+
+fn check_name(name: &str) -> Result<(), CommandError> {
+    if NAME.is_match(name) {
+        return Ok(());
+    }
+    let name = name.to_string();
+    Err(CommandError::InvalidName { name })
+}
+
+fn check_description(description: &str) -> Result<(), CommandError> {
+    if description.trim().is_empty() || validation::exceeds(description, MAX_DESCRIPTION_LENGTH) {
+        return Err(CommandError::InvalidDescription);
+    }
+    Ok(())
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidName { name } => write!(
+                f,
+                "Invalid name {name}, names must be 1 to 32 lowercase letters, digits, - or _"
+            ),
+            Self::InvalidDescription => write!(
+                f,
+                "Descriptions must be 1 to {MAX_DESCRIPTION_LENGTH} characters"
+            ),
+            Self::TooManyOptions => write!(f, "Commands may have at most {MAX_OPTIONS} options"),
+            Self::DuplicateOption { name } => write!(f, "The option {name} is given twice"),
+            Self::RequiredAfterOptional { name } => write!(
+                f,
+                "The required option {name} must come before optional options"
+            ),
+            Self::UnknownOption { name } => write!(f, "The command has no option {name}"),
+            Self::MissingOption { name } => write!(f, "The option {name} is required"),
+            Self::InvalidOptionValue { name, kind } => {
+                write!(f, "The option {name} must be a {}", kind.name())
+            }
+        }
+    }
+}
@@ -0,0 +1,292 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+
+use reqwest::Url;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use super::validation;
+
+const MAX_ROWS: usize = 5;
+const MAX_BUTTONS: usize = 5;
+const MAX_CUSTOM_ID_LENGTH: usize = 100;
+const MAX_LABEL_LENGTH: usize = 80;
+const MAX_SELECT_OPTIONS: usize = 25;
+const MAX_OPTION_LENGTH: usize = 100;
+const MAX_PLACEHOLDER_LENGTH: usize = 150;
+
+/// A row of interactive components under a message sent by a bot.
+///
+/// Rows hold up to five buttons, or a single select menu.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct ActionRow {
+    pub components: Vec<Component>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Component {
+    /// Link buttons open their `url`, other buttons send their `custom_id` to the bot.
+    Button {
+        style: ButtonStyle,
+        label: String,
+        custom_id: Option<String>,
+        url: Option<String>,
+        #[serde(default)]
+        disabled: bool,
+    },
+    Select {
+        custom_id: String,
+        placeholder: Option<String>,
+        options: Vec<SelectOption>,
+        #[serde(default = "one")]
+        min_values: usize,
+        #[serde(default = "one")]
+        max_values: usize,
+        #[serde(default)]
+        disabled: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum ButtonStyle {
+    #[serde(rename = "primary")]
+    Primary,
+    #[serde(rename = "secondary")]
+    Secondary,
+    #[serde(rename = "success")]
+    Success,
+    #[serde(rename = "danger")]
+    Danger,
+    #[serde(rename = "link")]
+    Link,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct SelectOption {
+    pub label: String,
+    pub value: String,
+    pub description: Option<String>,
+}
+
+/// A click on a button, or the values picked in a select menu.
+#[derive(Debug, Deserialize, Schema)]
+pub struct ComponentUse {
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Schema)]
+pub enum ComponentError {
+    TooManyRows,
+    EmptyRow,
+    TooManyButtons,
+    SelectNotAlone,
+    InvalidCustomId,
+    DuplicateCustomId { custom_id: String },
+    InvalidLinkButton,
+    InvalidButton,
+    TooLong { field: String, max: usize },
+    InvalidSelectOptions,
+    DuplicateValue { value: String },
+    InvalidValueRange,
+    Disabled,
+    InvalidValues,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl ActionRow {
+    /// Checks the components of a message against the layout and size limits.
+    pub fn validate(rows: &[ActionRow]) -> Result<(), ComponentError> {
+        if rows.len() > MAX_ROWS {
+            return Err(ComponentError::TooManyRows);
+        }
+
+        let mut custom_ids: Vec<&str> = vec![];
+        for row in rows {
+            match row.components.as_slice() {
+                [] => return Err(ComponentError::EmptyRow),
+                [Component::Select { .. }] => {}
+                components if components.len() > MAX_BUTTONS => {
+                    return Err(ComponentError::TooManyButtons)
+                }
+                components => {
+                    if components
+                        .iter()
+                        .any(|it| matches!(it, Component::Select { .. }))
+                    {
+                        return Err(ComponentError::SelectNotAlone);
+                    }
+                }
+            }
+
+            for component in &row.components {
+                component.validate()?;
+                if let Some(custom_id) = component.custom_id() {
+                    if custom_ids.contains(&custom_id) {
+                        let custom_id = custom_id.to_string();
+                        return Err(ComponentError::DuplicateCustomId { custom_id });
+                    }
+                    custom_ids.push(custom_id);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Finds the component with a custom id in the rows of a message.
+    pub fn find<'a>(rows: &'a [ActionRow], custom_id: &str) -> Option<&'a Component> {
+        rows.iter()
+            .flat_map(|it| &it.components)
+            .find(|it| it.custom_id() == Some(custom_id))
+    }
+}
+
+impl Component {
+    pub fn custom_id(&self) -> Option<&str> {
+        match self {
+            Self::Button { custom_id, .. } => custom_id.as_deref(),
+            Self::Select { custom_id, .. } => Some(custom_id),
+        }
+    }
+
+    fn validate(&self) -> Result<(), ComponentError> {
+        if let Some(custom_id) = self.custom_id() {
+            if custom_id.is_empty() || validation::exceeds(custom_id, MAX_CUSTOM_ID_LENGTH) {
+                return Err(ComponentError::InvalidCustomId);
+            }
+        }
+
+        match self {
+            Self::Button {
+                style,
+                label,
+                custom_id,
+                url,
+                ..
+            } => {
+                check_length("label", label, MAX_LABEL_LENGTH)?;
+                match (style, custom_id, url) {
+                    (ButtonStyle::Link, None, Some(url)) => {
+                        let is_http =
+                            Url::parse(url).is_ok_and(|it| matches!(it.scheme(), "http" | "https"));
+                        if !is_http {
+                            return Err(ComponentError::InvalidLinkButton);
+                        }
+                    }
+                    (ButtonStyle::Link, _, _) => return Err(ComponentError::InvalidLinkButton),
+                    (_, Some(_), None) => {}
+                    _ => return Err(ComponentError::InvalidButton),
+                }
+            }
+            Self::Select {
+                placeholder,
+                options,
+                min_values,
+                max_values,
+                ..
+            } => {
+                if options.is_empty() || options.len() > MAX_SELECT_OPTIONS {
+                    return Err(ComponentError::InvalidSelectOptions);
+                }
+                if let Some(placeholder) = placeholder {
+                    check_length("placeholder", placeholder, MAX_PLACEHOLDER_LENGTH)?;
+                }
+                for (i, option) in options.iter().enumerate() {
+                    check_length("option label", &option.label, MAX_OPTION_LENGTH)?;
+                    check_length("option value", &option.value, MAX_OPTION_LENGTH)?;
+                    if let Some(description) = &option.description {
+                        check_length("option description", description, MAX_OPTION_LENGTH)?;
+                    }
+                    if options[..i].iter().any(|it| it.value == option.value) {
+                        let value = option.value.clone();
+                        return Err(ComponentError::DuplicateValue { value });
+                    }
+                }
+                if min_values > max_values || *max_values == 0 || *max_values > options.len() {
+                    return Err(ComponentError::InvalidValueRange);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the values sent when the component is used.
+    ///
+    /// Buttons take no values, select menus take distinct values of their options.
+    pub fn check_use(&self, values: &[String]) -> Result<(), ComponentError> {
+        match self {
+            Self::Button { disabled: true, .. } | Self::Select { disabled: true, .. } => {
+                Err(ComponentError::Disabled)
+            }
+            Self::Button { .. } if values.is_empty() => Ok(()),
+            Self::Button { .. } => Err(ComponentError::InvalidValues),
+            Self::Select {
+                options,
+                min_values,
+                max_values,
+                ..
+            } => {
+                let in_range = (*min_values..=*max_values).contains(&values.len());
+                let known = values.iter().enumerate().all(|(i, value)| {
+                    !values[..i].contains(value) && options.iter().any(|it| it.value == *value)
+                });
+                if !in_range || !known {
+                    return Err(ComponentError::InvalidValues);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn check_length(field: &str, value: &str, max: usize) -> Result<(), ComponentError> {
+    if value.trim().is_empty() || validation::exceeds(value, max) {
+        let field = field.to_string();
+        return Err(ComponentError::TooLong { field, max });
+    }
+    Ok(())
+}
+
+impl Display for ComponentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyRows => write!(f, "Messages may have at most {MAX_ROWS} component rows"),
+            Self::EmptyRow => write!(f, "Component rows must not be empty"),
+            Self::TooManyButtons => write!(f, "Rows may have at most {MAX_BUTTONS} buttons"),
+            Self::SelectNotAlone => write!(f, "Select menus must be alone in their row"),
+            Self::InvalidCustomId => write!(
+                f,
+                "Custom ids must be 1 to {MAX_CUSTOM_ID_LENGTH} characters"
+            ),
+            Self::DuplicateCustomId { custom_id } => {
+                write!(f, "The custom id {custom_id} is used twice")
+            }
+            Self::InvalidLinkButton => write!(
+                f,
+                "Link buttons must have an http or https url and no custom id"
+            ),
+            Self::InvalidButton => write!(f, "Buttons must have a custom id and no url"),
+            Self::TooLong { field, max } => write!(f, "The {field} must be 1 to {max} characters"),
+            Self::InvalidSelectOptions => write!(
+                f,
+                "Select menus must have 1 to {MAX_SELECT_OPTIONS} options"
+            ),
+            Self::DuplicateValue { value } => write!(f, "The option value {value} is used twice"),
+            Self::InvalidValueRange => write!(
+                f,
+                "The minimum and maximum values must be within the number of options"
+            ),
+            Self::Disabled => write!(f, "The component is disabled"),
+            Self::InvalidValues => write!(f, "The values do not match the component"),
+        }
+    }
+}
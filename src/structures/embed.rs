@@ -0,0 +1,517 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    fmt::Display,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use reqwest::{header::LOCATION, redirect::Policy, Url};
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{database, with_lock};
+
+use super::{
+    client::ClientHolder,
+    event::Event,
+    markdown::{self, Node},
+    message::{Message, MessageResponse},
+    validation,
+};
+
+static META: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<meta\s[^>]*>").unwrap());
+static LINK_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<link\s[^>]*>").unwrap());
+static ATTRIBUTE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?is)([a-z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)')"#).unwrap());
+static TITLE: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap());
+
+const MAX_LINKS: usize = 5;
+const MAX_REDIRECTS: usize = 3;
+const MAX_BODY_SIZE: usize = 512 * 1024;
+const MAX_FIELD_LENGTH: usize = 512;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+const MAX_EMBEDS: usize = 10;
+const MAX_TITLE_LENGTH: usize = 256;
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME_LENGTH: usize = 256;
+const MAX_FIELD_VALUE_LENGTH: usize = 1024;
+const MAX_FOOTER_LENGTH: usize = 2048;
+const MAX_TOTAL_LENGTH: usize = 6000;
+const MAX_COLOR: u32 = 0xFF_FF_FF;
+
+/// A preview of a link in a message, or a structured embed sent by a bot.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Schema)]
+pub struct Embed {
+    pub url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub site_name: Option<String>,
+    pub image: Option<String>,
+    /// An RGB color, such as `0xFF8800`.
+    pub color: Option<u32>,
+    #[serde(default)]
+    pub fields: Vec<EmbedField>,
+    pub footer: Option<String>,
+    pub timestamp: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct EmbedField {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub inline: bool,
+}
+
+#[derive(Debug, Schema)]
+pub enum EmbedError {
+    TooManyEmbeds,
+    TooManyFields,
+    EmptyField,
+    TooLong { field: String, max: usize },
+    TooLarge,
+    InvalidColor,
+    InvalidTimestamp,
+    InvalidUrl,
+}
+
+impl Embed {
+    /// Checks embeds sent with a message against the size limits, which apply to all of them
+    /// together for the total length.
+    ///
+    /// Timestamps are normalized to UTC.
+    pub fn validate(embeds: &mut [Embed]) -> Result<(), EmbedError> {
+        if embeds.len() > MAX_EMBEDS {
+            return Err(EmbedError::TooManyEmbeds);
+        }
+        let mut total = 0;
+        for embed in embeds {
+            total += embed.validate_one()?;
+        }
+        if total > MAX_TOTAL_LENGTH {
+            return Err(EmbedError::TooLarge);
+        }
+        Ok(())
+    }
+
+    /// Keeps the previews that still fit next to the embeds a message was sent with.
+    fn fit_previews(existing: &[Embed], previews: Vec<Embed>) -> Vec<Embed> {
+        let room = MAX_EMBEDS.saturating_sub(existing.len());
+        let mut total: usize = existing
+            .iter()
+            .map(|it| it.clone().validate_one().unwrap_or(0))
+            .sum();
+
+        let mut fitting = vec![];
+        for mut preview in previews {
+            if fitting.len() == room {
+                break;
+            }
+            let Ok(length) = preview.validate_one() else {
+                continue;
+            };
+            if total + length <= MAX_TOTAL_LENGTH {
+                total += length;
+                fitting.push(preview);
+            }
+        }
+        fitting
+    }
+
+    /// Checks a single embed, returning the length of its text.
+    fn validate_one(&mut self) -> Result<usize, EmbedError> {
+        if self.fields.len() > MAX_FIELDS {
+            return Err(EmbedError::TooManyFields);
+        }
+
+        let mut total = 0;
+        let mut check = |field: &str, value: &str, max: usize| {
+            let length = validation::length(value);
+            total += length;
+            if length > max || value.len() > validation::max_bytes(max) {
+                let field = field.to_string();
+                return Err(EmbedError::TooLong { field, max });
+            }
+            Ok(())
+        };
+
+        check(
+            "title",
+            self.title.as_deref().unwrap_or(""),
+            MAX_TITLE_LENGTH,
+        )?;
+        check(
+            "description",
+            self.description.as_deref().unwrap_or(""),
+            MAX_DESCRIPTION_LENGTH,
+        )?;
+        check(
+            "site name",
+            self.site_name.as_deref().unwrap_or(""),
+            MAX_TITLE_LENGTH,
+        )?;
+        check(
+            "footer",
+            self.footer.as_deref().unwrap_or(""),
+            MAX_FOOTER_LENGTH,
+        )?;
+        for field in &self.fields {
+            if field.name.trim().is_empty() || field.value.trim().is_empty() {
+                return Err(EmbedError::EmptyField);
+            }
+            check("field name", &field.name, MAX_FIELD_NAME_LENGTH)?;
+            check("field value", &field.value, MAX_FIELD_VALUE_LENGTH)?;
+        }
+
+        if self.color.is_some_and(|it| it > MAX_COLOR) {
+            return Err(EmbedError::InvalidColor);
+        }
+
+        for url in [&self.url, &self.image].into_iter().flatten() {
+            let is_http = Url::parse(url).is_ok_and(|it| matches!(it.scheme(), "http" | "https"));
+            if !is_http {
+                return Err(EmbedError::InvalidUrl);
+            }
+        }
+
+        if let Some(timestamp) = &self.timestamp {
+            let Ok(timestamp) = DateTime::parse_from_rfc3339(timestamp) else {
+                return Err(EmbedError::InvalidTimestamp);
+            };
+            self.timestamp = Some(timestamp.with_timezone(&Utc).to_rfc3339());
+        }
+
+        Ok(total)
+    }
+
+    /// Finds the links in message content that should get previews.
+    pub fn find_links(content: &str) -> Vec<String> {
+        let mut links = vec![];
+        if let Ok(nodes) = markdown::parse(content) {
+            Self::collect_links(&nodes, &mut links);
+        }
+        links.truncate(MAX_LINKS);
+        links
+    }
+
+    fn collect_links(nodes: &[Node], links: &mut Vec<String>) {
+        for node in nodes {
+            match node {
+                Node::Link { url, .. } if !links.contains(url) => links.push(url.clone()),
+                Node::Bold { children } | Node::Italic { children } => {
+                    Self::collect_links(children, links);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Fetches previews for the links in a message, then adds them and sends a `MessageUpdate`.
+    pub fn resolve_in_background(message: Message, clients: ClientHolder) {
+        let links = Self::find_links(&message.content);
+        if links.is_empty() {
+            return;
+        }
+
+        tokio::spawn(async move {
+            let mut embeds = vec![];
+            for link in links {
+                if let Some(embed) = Self::fetch(&link).await {
+                    embeds.push(embed);
+                }
+            }
+
+            // Embeds are only set when a message is sent, so this is all the room there is.
+            let embeds = Self::fit_previews(&message.embeds, embeds);
+            if embeds.is_empty() {
+                return;
+            }
+
+            let database = database().await;
+            let Ok(Some(message)) = database.push_message_embeds(message.id, embeds).await else {
+                return;
+            };
+            let Ok(Some(channel)) = database.fetch_channel(message.channel_id.clone()).await else {
+                return;
+            };
+
+            let users = channel.get_users().await.unwrap_or(vec![]);
+            let resp = MessageResponse::from_message(message, channel).await;
+
+            with_lock!(clients).dispatch_users(users, &Event::MessageUpdate(resp));
+        });
+    }
+
+    /// Fetches the OpenGraph or oEmbed metadata of a page.
+    pub async fn fetch(url: &str) -> Option<Self> {
+        let (url, body) = fetch_public(url, "text/html").await?;
+
+        let mut embed = Self {
+            url: Some(url.to_string()),
+            ..Default::default()
+        };
+
+        let mut oembed = None;
+
+        for tag in META.find_iter(&body) {
+            let attributes = attributes(tag.as_str());
+            let key = attribute(&attributes, "property").or(attribute(&attributes, "name"));
+            let (Some(key), Some(content)) = (key, attribute(&attributes, "content")) else {
+                continue;
+            };
+            let field = match key.to_lowercase().as_str() {
+                "og:title" => &mut embed.title,
+                "og:description" | "description" => &mut embed.description,
+                "og:site_name" => &mut embed.site_name,
+                "og:image" => &mut embed.image,
+                _ => continue,
+            };
+            if field.is_none() {
+                *field = Some(truncate(&decode_entities(&content)));
+            }
+        }
+
+        for tag in LINK_TAG.find_iter(&body) {
+            let attributes = attributes(tag.as_str());
+            if attribute(&attributes, "type").as_deref() == Some("application/json+oembed") {
+                oembed = attribute(&attributes, "href").and_then(|it| url.join(&it).ok());
+                break;
+            }
+        }
+
+        if embed.title.is_none() {
+            if let Some(oembed) = oembed {
+                embed.merge_oembed(oembed.as_str()).await;
+            }
+        }
+
+        if embed.title.is_none() {
+            embed.title = TITLE
+                .captures(&body)
+                .map(|it| truncate(&decode_entities(it[1].trim())));
+        }
+
+        if embed.title.is_none() && embed.description.is_none() {
+            return None;
+        }
+
+        Some(embed)
+    }
+
+    async fn merge_oembed(&mut self, url: &str) {
+        let Some((_, body)) = fetch_public(url, "json").await else {
+            return;
+        };
+        let Ok(value) = serde_json::from_str::<Value>(&body) else {
+            return;
+        };
+        let field = |name: &str| value.get(name).and_then(Value::as_str).map(truncate);
+        self.title = self.title.take().or(field("title"));
+        self.site_name = self.site_name.take().or(field("provider_name"));
+        self.image = self.image.take().or(field("thumbnail_url"));
+    }
+}
+
+/// Fetches a page that must resolve to public addresses only, following a few redirects.
+///
+/// Returns the final URL and at most `MAX_BODY_SIZE` bytes of the body.
+async fn fetch_public(url: &str, content_type: &str) -> Option<(Url, String)> {
+    let mut url = Url::parse(url).ok()?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        if !matches!(url.scheme(), "http" | "https") {
+            return None;
+        }
+        let host = url.host_str()?.to_string();
+        let address = resolve_public(&host, url.port_or_known_default()?).await?;
+
+        let client = reqwest::Client::builder()
+            .redirect(Policy::none())
+            .timeout(TIMEOUT)
+            .resolve(&host, address)
+            .no_proxy()
+            .user_agent("Vulpark Link Previews")
+            .build()
+            .ok()?;
+
+        let mut response = client.get(url.clone()).send().await.ok()?;
+
+        if response.status().is_redirection() {
+            let location = response.headers().get(LOCATION)?.to_str().ok()?;
+            url = url.join(location).ok()?;
+            continue;
+        }
+
+        if !response.status().is_success() {
+            return None;
+        }
+
+        let is_expected = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|it| it.to_str().ok())
+            .is_some_and(|it| it.contains(content_type));
+        if !is_expected {
+            return None;
+        }
+
+        let mut body = vec![];
+        while let Ok(Some(chunk)) = response.chunk().await {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_SIZE {
+                body.truncate(MAX_BODY_SIZE);
+                break;
+            }
+        }
+
+        return Some((url, String::from_utf8_lossy(&body).to_string()));
+    }
+
+    None
+}
+
+/// Resolves a host, refusing it if any of its addresses are not public.
+async fn resolve_public(host: &str, port: u16) -> Option<SocketAddr> {
+    let addresses: Vec<SocketAddr> =
+        tokio::time::timeout(TIMEOUT, tokio::net::lookup_host((host, port)))
+            .await
+            .ok()?
+            .ok()?
+            .collect();
+
+    if addresses.is_empty() || !addresses.iter().all(|it| is_public(it.ip())) {
+        return None;
+    }
+
+    addresses.first().copied()
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_v4(ip);
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || ip.segments()[..6] == [0x64, 0xff9b, 0, 0, 0, 0])
+        }
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || a == 0
+        || a >= 240
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0)
+        || (a == 198 && (18..20).contains(&b)))
+}
+
+fn attributes(tag: &str) -> Vec<(String, String)> {
+    ATTRIBUTE
+        .captures_iter(tag)
+        .map(|it| {
+            let value = it.get(2).or(it.get(3)).map_or("", |it| it.as_str());
+            (it[1].to_lowercase(), value.to_string())
+        })
+        .collect()
+}
+
+fn attribute(attributes: &[(String, String)], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.clone())
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn truncate(value: &str) -> String {
+    value.chars().take(MAX_FIELD_LENGTH).collect()
+}
+
+impl Display for EmbedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooManyEmbeds => write!(f, "Messages may have at most {MAX_EMBEDS} embeds"),
+            Self::TooManyFields => write!(f, "Embeds may have at most {MAX_FIELDS} fields"),
+            Self::EmptyField => write!(f, "Embed field names and values must not be empty"),
+            Self::TooLong { field, max } => {
+                write!(f, "Embed {field}s may be at most {max} characters")
+            }
+            Self::TooLarge => write!(
+                f,
+                "Embeds may have at most {MAX_TOTAL_LENGTH} characters in total"
+            ),
+            Self::InvalidColor => write!(f, "Embed colors must be RGB values"),
+            Self::InvalidTimestamp => write!(f, "Embed timestamps must be RFC 3339 dates"),
+            Self::InvalidUrl => write!(f, "Embed links must use http or https"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn embed(description: &str) -> Embed {
+        Embed {
+            description: Some(description.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn total_length_spans_all_embeds() {
+        let half = "a".repeat(MAX_TOTAL_LENGTH / 2);
+        assert!(Embed::validate(&mut [embed(&half), embed(&half)]).is_ok());
+
+        let result = Embed::validate(&mut [embed(&half), embed(&half), embed("a")]);
+        assert!(matches!(result, Err(EmbedError::TooLarge)));
+    }
+
+    #[test]
+    fn previews_fit_next_to_existing_embeds() {
+        let existing = vec![embed("a"); MAX_EMBEDS - 1];
+        let fitting = Embed::fit_previews(&existing, vec![embed("b"), embed("c")]);
+        assert_eq!(fitting.len(), 1);
+
+        let full = vec![embed("a"); MAX_EMBEDS];
+        assert!(Embed::fit_previews(&full, vec![embed("b")]).is_empty());
+
+        let half = "a".repeat(MAX_TOTAL_LENGTH / 2);
+        let fitting = Embed::fit_previews(&[embed(&half)], vec![embed(&half), embed("b")]);
+        assert_eq!(fitting.len(), 1);
+        assert_eq!(fitting[0].description.as_deref(), Some(half.as_str()));
+    }
+}
@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Error;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{database, generate_ulid};
+
+use super::channel::Channel;
+
+static NAME: Lazy<Regex> = Lazy::new(|| Regex::new("^[A-Za-z0-9_]{2,32}$").unwrap());
+static SHORTCODE: Lazy<Regex> = Lazy::new(|| Regex::new(":([A-Za-z0-9_]{2,32}):").unwrap());
+static CODE: Lazy<Regex> = Lazy::new(|| Regex::new("(?s)```.*?```|`[^`]*`").unwrap());
+
+const CONTENT_TYPES: [&str; 4] = ["image/png", "image/gif", "image/webp", "image/jpeg"];
+
+/// A custom emoji of a guild. The image lives in blob storage under `id`.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct GuildEmoji {
+    pub id: String,
+    pub guild_id: String,
+    pub name: String,
+    pub content_type: String,
+    pub creator_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct GuildEmojisResponse {
+    pub guild_id: String,
+    pub emojis: Vec<GuildEmoji>,
+}
+
+/// An emoji along with its image, for downloading.
+#[derive(Debug, Serialize, Schema)]
+pub struct EmojiFile {
+    pub emoji: GuildEmoji,
+    pub data: Vec<u8>,
+}
+
+impl GuildEmoji {
+    pub fn new(guild_id: &str, name: &str, content_type: &str, creator_id: &str) -> Self {
+        Self {
+            id: generate_ulid(),
+            guild_id: guild_id.to_string(),
+            name: name.to_string(),
+            content_type: content_type.to_string(),
+            creator_id: creator_id.to_string(),
+        }
+    }
+
+    pub fn is_valid_name(name: &str) -> bool {
+        NAME.is_match(name)
+    }
+
+    pub fn is_valid_content_type(content_type: &str) -> bool {
+        CONTENT_TYPES.contains(&content_type)
+    }
+
+    /// The maximum size of an emoji image in bytes, from `MAX_EMOJI_SIZE`.
+    pub fn max_size() -> u64 {
+        std::env::var("MAX_EMOJI_SIZE")
+            .ok()
+            .and_then(|it| it.parse().ok())
+            .unwrap_or(256 * 1024)
+    }
+
+    pub async fn insert(self) -> Result<Self, Error> {
+        database().await.create_emoji(self).await
+    }
+
+    /// Replaces `:name:` with `<:name:id>` for the given emoji, leaving code untouched.
+    pub fn resolve(content: &str, emojis: &[GuildEmoji]) -> String {
+        if emojis.is_empty() {
+            return content.to_string();
+        }
+
+        let replace = |text: &str| {
+            SHORTCODE
+                .replace_all(text, |captures: &Captures| {
+                    match emojis.iter().find(|it| it.name == captures[1]) {
+                        Some(emoji) => format!("<:{}:{}>", emoji.name, emoji.id),
+                        None => captures[0].to_string(),
+                    }
+                })
+                .to_string()
+        };
+
+        let mut out = String::with_capacity(content.len());
+        let mut last = 0;
+        for code in CODE.find_iter(content) {
+            out.push_str(&replace(&content[last..code.start()]));
+            out.push_str(code.as_str());
+            last = code.end();
+        }
+        out.push_str(&replace(&content[last..]));
+        out
+    }
+
+    /// Resolves the emoji of the channel's guild in message content.
+    pub async fn resolve_in(content: &str, channel: &Channel) -> String {
+        let Some(guild) = channel.get_guild().await.option() else {
+            return content.to_string();
+        };
+        let emojis = database()
+            .await
+            .fetch_guild_emojis(&guild)
+            .await
+            .unwrap_or(vec![]);
+        Self::resolve(content, &emojis)
+    }
+}
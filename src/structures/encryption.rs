@@ -0,0 +1,275 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! End-to-end encryption of DM channels.
+//!
+//! Clients register devices with public keys, fetch the key bundles of the other members'
+//! devices to establish sessions, and send messages as one ciphertext envelope per device.
+//! The server only checks the shape of keys and envelopes, it never sees plaintext or private keys.
+
+use std::fmt::Display;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{database, generate_ulid};
+
+use super::{restricted_string::RestrictedString, validation::Field};
+
+pub const MAX_DEVICES: usize = 10;
+/// Older one-time prekeys are dropped when more than this many are uploaded.
+pub const MAX_ONE_TIME_PREKEYS: usize = 100;
+const MAX_KEY_SIZE: usize = 256;
+const MAX_CIPHERTEXT_SIZE: usize = 64 * 1024;
+const MAX_ENVELOPES: usize = 100;
+
+/// A device of a user, with the public keys other devices use to encrypt to it.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Device {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    /// The long-term public identity key, base64 encoded.
+    pub identity_key: String,
+    pub signed_prekey: SignedPreKey,
+    /// How many one-time prekeys are left to be claimed.
+    pub one_time_prekeys: usize,
+    pub created: String,
+}
+
+/// A medium-term public key, signed with the identity key of its device.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct SignedPreKey {
+    pub key_id: u32,
+    pub public_key: String,
+    pub signature: String,
+}
+
+/// A public key that is handed out once, to start a single session.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct PreKey {
+    pub key_id: u32,
+    pub public_key: String,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct DeviceCreate {
+    pub name: String,
+    pub identity_key: String,
+    pub signed_prekey: SignedPreKey,
+    #[serde(default)]
+    pub one_time_prekeys: Vec<PreKey>,
+}
+
+/// Rotates the signed prekey of a device, or adds one-time prekeys.
+#[derive(Debug, Deserialize, Schema)]
+pub struct PreKeysUpload {
+    pub signed_prekey: Option<SignedPreKey>,
+    #[serde(default)]
+    pub one_time_prekeys: Vec<PreKey>,
+}
+
+/// The keys needed to start a session with a device.
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct KeyBundle {
+    pub user_id: String,
+    pub device_id: String,
+    pub identity_key: String,
+    pub signed_prekey: SignedPreKey,
+    /// Only set when claimed, and while the device has some left.
+    pub one_time_prekey: Option<PreKey>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct KeyBundleQuery {
+    /// Claims a one-time prekey of each device, to start new sessions.
+    pub claim: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct DeviceListResponse {
+    pub user_id: String,
+    pub devices: Vec<Device>,
+}
+
+/// The content of a message in an encrypted channel, encrypted separately for each device.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct EncryptedContent {
+    pub sender_device_id: String,
+    pub envelopes: Vec<Envelope>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Envelope {
+    pub device_id: String,
+    /// Opaque to the server, base64 encoded.
+    pub ciphertext: String,
+}
+
+#[derive(Debug, Schema)]
+pub enum EncryptionError {
+    OnlyDms,
+    Required,
+    NotEncrypted,
+    Plaintext,
+    InvalidKey { field: String },
+    DuplicateKeyId { key_id: u32 },
+    UnknownDevice { device_id: String },
+    DuplicateEnvelope { device_id: String },
+    NoEnvelopes,
+    TooManyEnvelopes,
+    InvalidCiphertext,
+}
+
+impl Device {
+    pub async fn insert(self, one_time_prekeys: Vec<PreKey>) -> Result<Self, Error> {
+        database().await.create_device(self, one_time_prekeys).await
+    }
+}
+
+impl DeviceCreate {
+    /// Checks the keys, returning the device and its one-time prekeys.
+    pub fn validate(self, user_id: &str) -> Result<(Device, Vec<PreKey>), EncryptionError> {
+        check_key("identity key", &self.identity_key)?;
+        check_signed_prekey(&self.signed_prekey)?;
+        check_prekeys(&self.one_time_prekeys)?;
+
+        let device = Device {
+            id: generate_ulid(),
+            user_id: user_id.to_string(),
+            name: RestrictedString::space(&self.name, Field::DeviceName),
+            identity_key: self.identity_key,
+            signed_prekey: self.signed_prekey,
+            one_time_prekeys: self.one_time_prekeys.len().min(MAX_ONE_TIME_PREKEYS),
+            created: Utc::now().to_rfc3339(),
+        };
+        Ok((device, self.one_time_prekeys))
+    }
+}
+
+impl PreKeysUpload {
+    pub fn validate(&self) -> Result<(), EncryptionError> {
+        if let Some(signed_prekey) = &self.signed_prekey {
+            check_signed_prekey(signed_prekey)?;
+        }
+        check_prekeys(&self.one_time_prekeys)
+    }
+}
+
+impl KeyBundle {
+    pub fn new(device: Device, one_time_prekey: Option<PreKey>) -> Self {
+        Self {
+            user_id: device.user_id,
+            device_id: device.id,
+            identity_key: device.identity_key,
+            signed_prekey: device.signed_prekey,
+            one_time_prekey,
+        }
+    }
+}
+
+impl EncryptedContent {
+    /// Checks that the message is sent from a device of its author, with one envelope
+    /// per device at most, all for devices of members of the channel.
+    pub async fn validate(
+        &self,
+        author_id: &str,
+        members: &[String],
+    ) -> Result<Result<(), EncryptionError>, Error> {
+        if self.envelopes.is_empty() {
+            return Ok(Err(EncryptionError::NoEnvelopes));
+        }
+        if self.envelopes.len() > MAX_ENVELOPES {
+            return Ok(Err(EncryptionError::TooManyEnvelopes));
+        }
+
+        let devices = database().await.fetch_devices_of(members).await?;
+
+        let sender = devices.iter().find(|it| it.id == self.sender_device_id);
+        if sender.is_none_or(|it| it.user_id != author_id) {
+            let device_id = self.sender_device_id.clone();
+            return Ok(Err(EncryptionError::UnknownDevice { device_id }));
+        }
+
+        for (i, envelope) in self.envelopes.iter().enumerate() {
+            let device_id = envelope.device_id.clone();
+            if !devices.iter().any(|it| it.id == envelope.device_id) {
+                return Ok(Err(EncryptionError::UnknownDevice { device_id }));
+            }
+            if self.envelopes[..i]
+                .iter()
+                .any(|it| it.device_id == envelope.device_id)
+            {
+                return Ok(Err(EncryptionError::DuplicateEnvelope { device_id }));
+            }
+            let size = decoded_size(&envelope.ciphertext);
+            if size.is_none_or(|it| it == 0 || it > MAX_CIPHERTEXT_SIZE) {
+                return Ok(Err(EncryptionError::InvalidCiphertext));
+            }
+        }
+
+        Ok(Ok(()))
+    }
+}
+
+fn decoded_size(data: &str) -> Option<usize> {
+    STANDARD.decode(data).ok().map(|it| it.len())
+}
+
+fn check_key(field: &str, key: &str) -> Result<(), EncryptionError> {
+    if decoded_size(key).is_none_or(|it| it == 0 || it > MAX_KEY_SIZE) {
+        let field = field.to_string();
+        return Err(EncryptionError::InvalidKey { field });
+    }
+    Ok(())
+}
+
+fn check_signed_prekey(signed_prekey: &SignedPreKey) -> Result<(), EncryptionError> {
+    check_key("signed prekey", &signed_prekey.public_key)?;
+    check_key("signed prekey signature", &signed_prekey.signature)
+}
+
+fn check_prekeys(prekeys: &[PreKey]) -> Result<(), EncryptionError> {
+    for (i, prekey) in prekeys.iter().enumerate() {
+        check_key("one-time prekey", &prekey.public_key)?;
+        if prekeys[..i].iter().any(|it| it.key_id == prekey.key_id) {
+            let key_id = prekey.key_id;
+            return Err(EncryptionError::DuplicateKeyId { key_id });
+        }
+    }
+    Ok(())
+}
+
+impl Display for EncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OnlyDms => write!(f, "Only DM channels may be encrypted"),
+            Self::Required => write!(f, "Messages in this channel must be encrypted"),
+            Self::NotEncrypted => write!(f, "This channel is not encrypted"),
+            Self::Plaintext => write!(
+                f,
+                "Encrypted messages may not have plain content, attachments, polls or embeds"
+            ),
+            Self::InvalidKey { field } => write!(f, "The {field} is invalid"),
+            Self::DuplicateKeyId { key_id } => write!(f, "The key id {key_id} is used twice"),
+            Self::UnknownDevice { device_id } => {
+                write!(f, "No device {device_id} belongs to a member of the channel")
+            }
+            Self::DuplicateEnvelope { device_id } => {
+                write!(f, "The device {device_id} has more than one envelope")
+            }
+            Self::NoEnvelopes => write!(f, "Encrypted messages need at least one envelope"),
+            Self::TooManyEnvelopes => {
+                write!(f, "Messages may have at most {MAX_ENVELOPES} envelopes")
+            }
+            Self::InvalidCiphertext => write!(
+                f,
+                "Ciphertexts must be base64 encoded and at most {MAX_CIPHERTEXT_SIZE} bytes"
+            ),
+        }
+    }
+}
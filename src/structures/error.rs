@@ -6,7 +6,14 @@ use rweb::Schema;
 use serde::Serialize;
 use warp::Rejection;
 
-use super::{auth::AuthError, response::Response, WithStatus};
+use crate::storage::StorageError;
+
+use super::{
+    auth::AuthError, channel::AUTO_ARCHIVE_DURATIONS, command::CommandError,
+    component::ComponentError, embed::EmbedError, encryption::EncryptionError,
+    markdown::MarkdownError, poll::PollError, response::Response, validation::ValidationError,
+    WithStatus,
+};
 
 pub type ResponseResult<T> = Result<WithStatus<Response<T>>, Rejection>;
 
@@ -15,11 +22,55 @@ pub enum HttpError {
     InvalidLoginCredentials,
     NotFound(String),
     MessageContentEmpty,
+    SearchQueryEmpty,
+    InvalidReply,
+    InvalidNonce(usize),
+    InvalidExpiry { min: u64, max: u64 },
+    DuplicateNonce,
+    InvalidEmoji,
+    InvalidEmojiName,
+    InvalidEmojiImage(u64),
+    EmojiExists,
+    TooManyEmojis(u64),
+    TooManyRoles(u64),
+    GuildAccessDenied,
+    Command(CommandError),
+    TooManyCommands(u64),
+    NotABot,
+    BotsCannotCreateBots,
+    InteractionResponded,
+    Embed(EmbedError),
+    Component(ComponentError),
+    Encryption(EncryptionError),
+    TooManyDevices(usize),
+    Markdown(MarkdownError),
+    Poll(PollError),
+    NotAPoll,
+    PollClosed,
+    InvalidPollOption,
+    VoteConflict,
+    InvalidAttachment,
+    MissingAttachment,
+    AttachmentTooLarge(u64),
     ChannelAccessDenied,
+    AckBehind,
+    TooManyMessages(usize),
+    TooManyPins(u64),
+    InvalidThread,
+    ThreadExists,
+    InvalidAutoArchive,
+    InvalidChannelKind,
+    InvalidCategory,
+    InvalidPositions,
+    InvalidOverwrite,
+    InvalidSchedule,
+    TooManyScheduledMessages(u64),
     TooManyUsers,
     AccountAttached,
     AccountNotAttached,
     Oauth(AuthError),
+    Storage(StorageError),
+    Validation(ValidationError),
     Other(String),
 }
 
@@ -29,16 +80,95 @@ impl ToString for HttpError {
             Self::InvalidLoginCredentials => "Invalid login credentials.".to_string(),
             Self::NotFound(name) => format!("{name} not found."),
             Self::MessageContentEmpty => "Message content is empty.".to_string(),
+            Self::SearchQueryEmpty => "Search query is empty.".to_string(),
+            Self::InvalidReply => "Replies must be in the same channel".to_string(),
+            Self::InvalidNonce(max) => format!("Nonces may be at most {max} characters"),
+            Self::InvalidExpiry { min, max } => {
+                format!("Messages must expire after {min} to {max} seconds")
+            }
+            Self::DuplicateNonce => "A message with this nonce is still being sent".to_string(),
+            Self::InvalidEmoji => "Invalid emoji".to_string(),
+            Self::InvalidEmojiName => {
+                "Emoji names must be 2 to 32 letters, digits or underscores".to_string()
+            }
+            Self::InvalidEmojiImage(max) => {
+                format!("Emoji must be png, gif, webp or jpeg images of at most {max} bytes")
+            }
+            Self::EmojiExists => "This guild already has an emoji with this name".to_string(),
+            Self::TooManyEmojis(max) => format!("Guilds may have at most {max} emoji"),
+            Self::TooManyRoles(max) => format!("Guilds may have at most {max} roles"),
+            Self::Markdown(err) => err.to_string(),
+            Self::Poll(err) => err.to_string(),
+            Self::NotAPoll => "This message has no poll".to_string(),
+            Self::PollClosed => "This poll has closed".to_string(),
+            Self::InvalidPollOption => "This poll has no such option".to_string(),
+            Self::VoteConflict => {
+                "Another vote of yours on this poll was counted first".to_string()
+            }
+            Self::InvalidAttachment => "Attachments are invalid or already sent".to_string(),
+            Self::MissingAttachment => "No file was uploaded".to_string(),
+            Self::AttachmentTooLarge(max) => format!("Attachments may be at most {max} bytes"),
             Self::ChannelAccessDenied => "Channel access is denied".to_string(),
+            Self::AckBehind => {
+                "The channel is already read up to this message or a later one".to_string()
+            }
+            Self::GuildAccessDenied => "Guild access is denied".to_string(),
+            Self::Command(err) => err.to_string(),
+            Self::TooManyCommands(max) => format!("Bots may have at most {max} commands per guild"),
+            Self::NotABot => "Only bots may do this".to_string(),
+            Self::BotsCannotCreateBots => "Bots may not create other bots".to_string(),
+            Self::InteractionResponded => {
+                "This interaction was already responded to, or has expired".to_string()
+            }
+            Self::Embed(err) => err.to_string(),
+            Self::Component(err) => err.to_string(),
+            Self::Encryption(err) => err.to_string(),
+            Self::TooManyDevices(max) => format!("Users may have at most {max} devices"),
+            Self::TooManyMessages(max) => format!("Too many messages, the maximum is {max}"),
+            Self::InvalidSchedule => "Messages must be scheduled for a future time".to_string(),
+            Self::TooManyScheduledMessages(max) => {
+                format!("Users may have at most {max} scheduled messages")
+            }
+            Self::InvalidThread => {
+                "Threads must be started from a message in a guild channel".to_string()
+            }
+            Self::ThreadExists => "A thread was already started from this message".to_string(),
+            Self::InvalidAutoArchive => format!(
+                "Threads may be archived after {AUTO_ARCHIVE_DURATIONS:?} minutes of inactivity"
+            ),
+            Self::InvalidChannelKind => {
+                "Announcement channels and categories must be in a guild".to_string()
+            }
+            Self::InvalidCategory => {
+                "Categories must be in the same guild, and can not be nested".to_string()
+            }
+            Self::InvalidPositions => "Each channel may only be moved once".to_string(),
+            Self::InvalidOverwrite => {
+                "Only guild channels have overwrites, which may not both allow and deny a permission"
+                    .to_string()
+            }
+            Self::TooManyPins(max) => format!("Channels may have at most {max} pinned messages"),
             Self::TooManyUsers => "Too many users with the same username".to_string(),
             Self::AccountAttached => "This account is already attached to a user".to_string(),
             Self::AccountNotAttached => "This account is not attached to a user".to_string(),
             Self::Oauth(err) => err.to_string(),
+            Self::Storage(err) => err.to_string(),
+            Self::Validation(err) => err.to_string(),
             Self::Other(msg) => msg.to_string(),
         }
     }
 }
 
+impl HttpError {
+    /// What clients can act on, sent along with the message.
+    pub fn details(&self) -> Option<&ValidationError> {
+        match self {
+            Self::Validation(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
 impl Serialize for HttpError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -4,7 +4,19 @@
 
 use serde::Deserialize;
 
-use super::{channel::ChannelResponse, guild::GuildResponse, message::MessageResponse, user::User};
+use super::{
+    channel::{ChannelPinsResponse, ChannelResponse},
+    emoji::GuildEmojisResponse,
+    encryption::DeviceListResponse,
+    guild::GuildResponse,
+    interaction::Interaction,
+    message::{MessageDeleteBulkResponse, MessageDeleteResponse, MessageResponse},
+    poll::PollTally,
+    reaction::ReactionResponse,
+    read_state::ReadState,
+    role::{GuildMember, GuildRolesResponse},
+    user::User,
+};
 
 macro_rules! event {
     ($($name:ident $({ $($n: ident: $t_1:ty),* $(,)? })? $(($t_2:ty))? ),+ $(,)?) => {
@@ -70,13 +82,33 @@ event! {
         user: User,
     },
     MessageCreate (MessageResponse),
+    MessageUpdate (MessageResponse),
+    MessageDelete (MessageDeleteResponse),
+    MessageDeleteBulk (MessageDeleteBulkResponse),
+    ReactionAdd (ReactionResponse),
+    ReactionRemove (ReactionResponse),
+    PollUpdate (PollTally),
+    MentionCreate (MessageResponse),
+    ReadStateUpdate (ReadState),
     ChannelCreate (ChannelResponse),
+    ChannelUpdate (ChannelResponse),
+    ChannelDelete (ChannelResponse),
+    ChannelPinsUpdate (ChannelPinsResponse),
+    ThreadCreate (ChannelResponse),
+    ThreadUpdate (ChannelResponse),
     GuildCreate (GuildResponse),
+    GuildEmojisUpdate (GuildEmojisResponse),
+    GuildRolesUpdate (GuildRolesResponse),
+    GuildMemberUpdate (GuildMember),
+    InteractionCreate (Interaction),
+    ComponentInteraction (Interaction),
+    DeviceListUpdate (DeviceListResponse),
 }
 
 #[derive(Debug, Deserialize)]
 pub enum ReceivedEvent {
     Handshake { token: String },
+    Ack { channel_id: String, message_id: String },
 }
 
 impl ToString for Event {
@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::{database, generate_ulid};
 
-use super::{restricted_string::RestrictedString, user::User};
+use super::{restricted_string::RestrictedString, user::User, validation::Field};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Schema)]
 pub struct Guild {
@@ -31,7 +31,7 @@ impl Guild {
     pub fn new(name: &str, owner_id: &str) -> Self {
         Self {
             id: generate_ulid(),
-            name: RestrictedString::space(name),
+            name: RestrictedString::space(name, Field::GuildName),
             owner_id: owner_id.to_string(),
         }
     }
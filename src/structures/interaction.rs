@@ -0,0 +1,138 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use chrono::Utc;
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{database, generate_ulid};
+
+use super::{command::Command, component::ActionRow, embed::Embed, message::Message, user::User};
+
+/// An invocation of a command or a use of a message component, sent to the bot it belongs to.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Interaction {
+    pub id: String,
+    pub channel_id: String,
+    pub bot_id: String,
+    pub user: User,
+    pub data: InteractionData,
+    pub created: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InteractionData {
+    Command {
+        command_id: String,
+        command_name: String,
+        guild_id: String,
+        options: Vec<InteractionOption>,
+    },
+    /// A click on a button, or the values picked in a select menu, of a message sent by the bot.
+    Component {
+        message_id: String,
+        custom_id: String,
+        values: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct InteractionOption {
+    pub name: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct InteractionCreate {
+    pub channel_id: String,
+    pub command_id: String,
+    #[serde(default)]
+    pub options: Vec<InteractionOption>,
+}
+
+/// The response of a bot to an interaction, sent as a message in its channel.
+#[derive(Debug, Deserialize, Schema)]
+pub struct InteractionCallback {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
+    pub components: Vec<ActionRow>,
+}
+
+/// The interaction a message was sent in response to.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct MessageInteraction {
+    pub id: String,
+    /// The name of the command, or the custom id of the component.
+    #[serde(alias = "command_name")]
+    pub name: String,
+    pub user_id: String,
+}
+
+impl Interaction {
+    pub fn new(
+        command: &Command,
+        channel_id: String,
+        user: User,
+        options: Vec<InteractionOption>,
+    ) -> Self {
+        Self {
+            id: generate_ulid(),
+            channel_id,
+            bot_id: command.bot_id.clone(),
+            user,
+            data: InteractionData::Command {
+                command_id: command.id.clone(),
+                command_name: command.name.clone(),
+                guild_id: command.guild_id.clone(),
+                options,
+            },
+            created: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Creates an interaction for a use of a component of a message sent by a bot.
+    pub fn component(
+        message: &Message,
+        bot_id: String,
+        user: User,
+        custom_id: String,
+        values: Vec<String>,
+    ) -> Self {
+        Self {
+            id: generate_ulid(),
+            channel_id: message.channel_id.clone(),
+            bot_id,
+            user,
+            data: InteractionData::Component {
+                message_id: message.id.clone(),
+                custom_id,
+                values,
+            },
+            created: Utc::now().to_rfc3339(),
+        }
+    }
+
+    pub async fn insert(self) -> Result<Self, Error> {
+        database().await.create_interaction(self).await
+    }
+}
+
+impl From<&Interaction> for MessageInteraction {
+    fn from(value: &Interaction) -> Self {
+        Self {
+            id: value.id.clone(),
+            name: match &value.data {
+                InteractionData::Command { command_name, .. } => command_name.clone(),
+                InteractionData::Component { custom_id, .. } => custom_id.clone(),
+            },
+            user_id: value.user.id.clone(),
+        }
+    }
+}
@@ -0,0 +1,495 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+static LINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\[([^\]\n]*)\]\(([^)\s]+)\)").unwrap());
+static AUTOLINK: Lazy<Regex> = Lazy::new(|| Regex::new(r"^https?://[^\s<>]+").unwrap());
+static USER_MENTION: Lazy<Regex> = Lazy::new(|| Regex::new("^<@([0-9A-Z]{26})>").unwrap());
+static CHANNEL_MENTION: Lazy<Regex> = Lazy::new(|| Regex::new("^<#([0-9A-Z]{26})>").unwrap());
+static CUSTOM_EMOJI: Lazy<Regex> =
+    Lazy::new(|| Regex::new("^<:([A-Za-z0-9_]{2,32}):([0-9A-Z]{26})>").unwrap());
+static LANGUAGE: Lazy<Regex> = Lazy::new(|| Regex::new("^[A-Za-z0-9_+#.-]{1,32}$").unwrap());
+
+const MAX_DEPTH: usize = 16;
+/// Longer content is kept as plain text, so parsing stays cheap.
+const MAX_PARSE_LENGTH: usize = 64 * 1024;
+/// Longer delimiters first, so `**` is not read as two `*`.
+const DELIMITERS: [&str; 4] = ["**", "||", "*", "_"];
+
+/// A node of the message markdown dialect.
+///
+/// Supported syntax is `**bold**`, `*italics*` or `_italics_`, `` `code` ``,
+/// ```` ```language code blocks``` ````, `||spoilers||`, `[links](https://...)`,
+/// bare `https://` links, mentions and `<:name:id>` custom emoji.
+#[derive(Debug, Clone, Serialize, Schema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Node {
+    Text {
+        content: String,
+    },
+    Bold {
+        children: Vec<Node>,
+    },
+    Italic {
+        children: Vec<Node>,
+    },
+    Spoiler {
+        children: Vec<Node>,
+    },
+    Code {
+        content: String,
+    },
+    CodeBlock {
+        language: Option<String>,
+        content: String,
+    },
+    Link {
+        url: String,
+        children: Vec<Node>,
+    },
+    UserMention {
+        id: String,
+    },
+    ChannelMention {
+        id: String,
+    },
+    Everyone,
+    CustomEmoji {
+        name: String,
+        id: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Schema)]
+pub enum RenderFormat {
+    #[serde(rename = "ast")]
+    Ast,
+    #[serde(rename = "html")]
+    Html,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct RenderedContent {
+    pub ast: Option<Vec<Node>>,
+    pub html: Option<String>,
+}
+
+#[derive(Debug, Schema)]
+pub enum MarkdownError {
+    UnsafeLink(String),
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+/// A delimiter that is still open, with the nodes parsed since it.
+struct Frame {
+    delimiter: &'static str,
+    nodes: Vec<Node>,
+}
+
+/// Parses message content.
+///
+/// Unterminated or overly nested formatting is kept as plain text, but links
+/// that are not `http` or `https` are rejected. Content over `MAX_PARSE_LENGTH`
+/// bytes is not parsed at all.
+pub fn parse(content: &str) -> Result<Vec<Node>, MarkdownError> {
+    if content.len() > MAX_PARSE_LENGTH {
+        return Ok(vec![Node::Text {
+            content: content.to_string(),
+        }]);
+    }
+    Parser::new(content, 0).parse_all()
+}
+
+pub fn render(content: &str, format: RenderFormat) -> Option<RenderedContent> {
+    let nodes = parse(content).ok()?;
+    Some(match format {
+        RenderFormat::Ast => RenderedContent {
+            ast: Some(nodes),
+            html: None,
+        },
+        RenderFormat::Html => RenderedContent {
+            ast: None,
+            html: Some(to_html(&nodes)),
+        },
+    })
+}
+
+impl<'a> Parser<'a> {
+    fn new(src: &'a str, depth: usize) -> Self {
+        Self { src, pos: 0, depth }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    /// Parses the content in a single pass, so no text is ever parsed twice.
+    ///
+    /// A delimiter closes the innermost open delimiter of the same kind. Delimiters
+    /// left open in between, or at the end, are kept as text.
+    fn parse_all(&mut self) -> Result<Vec<Node>, MarkdownError> {
+        let mut frames = vec![Frame {
+            delimiter: "",
+            nodes: vec![],
+        }];
+
+        while !self.rest().is_empty() {
+            let depth = self.depth + frames.len() - 1;
+
+            if let Some(node) = self.parse_node(depth)? {
+                push_node(&mut frames, node);
+                continue;
+            }
+
+            let rest = self.rest();
+            if let Some(delimiter) = DELIMITERS.into_iter().find(|it| rest.starts_with(it)) {
+                self.parse_delimiter(&mut frames, delimiter);
+                continue;
+            }
+
+            let char = rest.chars().next().unwrap();
+            push_node(
+                &mut frames,
+                Node::Text {
+                    content: char.to_string(),
+                },
+            );
+            self.pos += char.len_utf8();
+        }
+
+        while frames.len() > 1 {
+            collapse(&mut frames);
+        }
+        Ok(frames.pop().unwrap().nodes)
+    }
+
+    /// Closes the innermost open `delimiter`, or opens a new one.
+    ///
+    /// Intraword underscores only close, and a delimiter closed right away is kept as text.
+    fn parse_delimiter(&mut self, frames: &mut Vec<Frame>, delimiter: &'static str) {
+        let can_open = delimiter != "_" || !self.src[..self.pos].ends_with(char::is_alphanumeric);
+        self.pos += delimiter.len();
+
+        if let Some(open) = frames.iter().rposition(|it| it.delimiter == delimiter) {
+            while frames.len() > open + 1 {
+                collapse(frames);
+            }
+            let frame = frames.pop().unwrap();
+            if !frame.nodes.is_empty() {
+                let children = frame.nodes;
+                let node = match delimiter {
+                    "**" => Node::Bold { children },
+                    "||" => Node::Spoiler { children },
+                    _ => Node::Italic { children },
+                };
+                push_node(frames, node);
+                return;
+            }
+            let content = delimiter.to_string();
+            push_node(frames, Node::Text { content });
+        }
+
+        if can_open && self.depth + frames.len() - 1 < MAX_DEPTH {
+            frames.push(Frame {
+                delimiter,
+                nodes: vec![],
+            });
+        } else {
+            let content = delimiter.to_string();
+            push_node(frames, Node::Text { content });
+        }
+    }
+
+    /// Parses a node other than formatting at the current position, if one starts here.
+    fn parse_node(&mut self, depth: usize) -> Result<Option<Node>, MarkdownError> {
+        let rest = self.rest();
+
+        if let Some(char) = rest.strip_prefix('\\').and_then(|it| it.chars().next()) {
+            if !char.is_alphanumeric() {
+                self.pos += 1 + char.len_utf8();
+                return Ok(Some(Node::Text {
+                    content: char.to_string(),
+                }));
+            }
+        }
+
+        if let Some(code) = rest.strip_prefix("```") {
+            if let Some(end) = code.find("```") {
+                self.pos += end + 6;
+                return Ok(Some(code_block(&code[..end])));
+            }
+        }
+
+        if let Some(code) = rest.strip_prefix('`') {
+            if let Some(end) = code.find('`').filter(|it| *it > 0) {
+                self.pos += end + 2;
+                return Ok(Some(Node::Code {
+                    content: code[..end].to_string(),
+                }));
+            }
+        }
+
+        if let Some(link) = AUTOLINK.find(rest) {
+            let url = link
+                .as_str()
+                .trim_end_matches(['.', ',', ')', '!', '?', ';', ':', '\'', '"']);
+            self.pos += url.len();
+            return Ok(Some(Node::Link {
+                url: url.to_string(),
+                children: vec![],
+            }));
+        }
+
+        if let Some(captures) = USER_MENTION.captures(rest) {
+            self.pos += captures[0].len();
+            return Ok(Some(Node::UserMention {
+                id: captures[1].to_string(),
+            }));
+        }
+
+        if let Some(captures) = CHANNEL_MENTION.captures(rest) {
+            self.pos += captures[0].len();
+            return Ok(Some(Node::ChannelMention {
+                id: captures[1].to_string(),
+            }));
+        }
+
+        if let Some(captures) = CUSTOM_EMOJI.captures(rest) {
+            self.pos += captures[0].len();
+            return Ok(Some(Node::CustomEmoji {
+                name: captures[1].to_string(),
+                id: captures[2].to_string(),
+            }));
+        }
+
+        if rest.starts_with("@everyone") {
+            self.pos += "@everyone".len();
+            return Ok(Some(Node::Everyone));
+        }
+
+        if depth >= MAX_DEPTH {
+            return Ok(None);
+        }
+
+        if let Some(captures) = LINK.captures(rest) {
+            let url = captures[2].to_string();
+            check_link(&url)?;
+            let children = Parser::new(&captures[1], depth + 1).parse_all()?;
+            self.pos += captures[0].len();
+            return Ok(Some(Node::Link { url, children }));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Adds a node to the innermost open delimiter, merging text.
+fn push_node(frames: &mut [Frame], node: Node) {
+    let nodes = &mut frames.last_mut().unwrap().nodes;
+    if let (Some(Node::Text { content: last }), Node::Text { content }) = (nodes.last_mut(), &node)
+    {
+        last.push_str(content);
+        return;
+    }
+    nodes.push(node);
+}
+
+/// Gives up on the innermost open delimiter, keeping it as text.
+fn collapse(frames: &mut Vec<Frame>) {
+    let frame = frames.pop().unwrap();
+    push_node(
+        frames,
+        Node::Text {
+            content: frame.delimiter.to_string(),
+        },
+    );
+    for node in frame.nodes {
+        push_node(frames, node);
+    }
+}
+
+fn code_block(code: &str) -> Node {
+    if let Some((first, rest)) = code.split_once('\n') {
+        if LANGUAGE.is_match(first) {
+            return Node::CodeBlock {
+                language: Some(first.to_string()),
+                content: rest.to_string(),
+            };
+        }
+    }
+    Node::CodeBlock {
+        language: None,
+        content: code.trim_start_matches('\n').to_string(),
+    }
+}
+
+fn check_link(url: &str) -> Result<(), MarkdownError> {
+    let lower = url.to_lowercase();
+    if lower.starts_with("https://") || lower.starts_with("http://") {
+        return Ok(());
+    }
+    Err(MarkdownError::UnsafeLink(url.to_string()))
+}
+
+pub fn to_html(nodes: &[Node]) -> String {
+    let mut out = String::new();
+    for node in nodes {
+        match node {
+            Node::Text { content } => out.push_str(&escape(content).replace('\n', "<br>")),
+            Node::Bold { children } => {
+                out.push_str(&format!("<strong>{}</strong>", to_html(children)))
+            }
+            Node::Italic { children } => out.push_str(&format!("<em>{}</em>", to_html(children))),
+            Node::Spoiler { children } => {
+                out.push_str(&format!(
+                    "<span class=\"spoiler\">{}</span>",
+                    to_html(children)
+                ));
+            }
+            Node::Code { content } => out.push_str(&format!("<code>{}</code>", escape(content))),
+            Node::CodeBlock { language, content } => {
+                let class = language
+                    .as_ref()
+                    .map(|it| format!(" class=\"language-{}\"", escape(it)))
+                    .unwrap_or_default();
+                out.push_str(&format!(
+                    "<pre><code{class}>{}</code></pre>",
+                    escape(content)
+                ));
+            }
+            Node::Link { url, children } => {
+                let text = if children.is_empty() {
+                    escape(url)
+                } else {
+                    to_html(children)
+                };
+                out.push_str(&format!(
+                    "<a href=\"{}\" rel=\"noopener noreferrer nofollow\" target=\"_blank\">{text}</a>",
+                    escape(url)
+                ));
+            }
+            Node::UserMention { id } => {
+                out.push_str(&format!(
+                    "<span class=\"mention\" data-user-id=\"{id}\">&lt;@{id}&gt;</span>"
+                ));
+            }
+            Node::ChannelMention { id } => {
+                out.push_str(&format!(
+                    "<span class=\"mention\" data-channel-id=\"{id}\">&lt;#{id}&gt;</span>"
+                ));
+            }
+            Node::Everyone => out.push_str("<span class=\"mention\">@everyone</span>"),
+            Node::CustomEmoji { name, id } => {
+                out.push_str(&format!(
+                    "<img class=\"emoji\" alt=\":{}:\" src=\"/emojis/{}\">",
+                    escape(name),
+                    escape(id)
+                ));
+            }
+        }
+    }
+    out
+}
+
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for char in value.chars() {
+        match char {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(char),
+        }
+    }
+    out
+}
+
+impl Display for MarkdownError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnsafeLink(url) => write!(f, "Links must use http or https: {url}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn html(content: &str) -> String {
+        to_html(&parse(content).unwrap())
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(
+            html("**a** *b* _c_ ||d||"),
+            "<strong>a</strong> <em>b</em> <em>c</em> <span class=\"spoiler\">d</span>"
+        );
+        assert_eq!(html("**a *b* c**"), "<strong>a <em>b</em> c</strong>");
+        assert_eq!(html("`*a*` \\*b*"), "<code>*a*</code> *b*");
+    }
+
+    #[test]
+    fn unterminated_formatting_is_text() {
+        assert_eq!(html("**a *b"), "**a *b");
+        assert_eq!(html("****"), "****");
+        assert_eq!(html("*a **b* c"), "<em>a **b</em> c");
+        assert_eq!(html("snake_case_name"), "snake_case_name");
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let content = format!("{}a{}", "||*".repeat(20), "*||".repeat(20));
+        let nodes = parse(&content).unwrap();
+        fn depth(nodes: &[Node]) -> usize {
+            nodes
+                .iter()
+                .map(|it| match it {
+                    Node::Bold { children }
+                    | Node::Italic { children }
+                    | Node::Spoiler { children }
+                    | Node::Link { children, .. } => 1 + depth(children),
+                    _ => 0,
+                })
+                .max()
+                .unwrap_or(0)
+        }
+        assert!(depth(&nodes) <= MAX_DEPTH);
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            html("[**a**](https://example.com)"),
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\" target=\"_blank\"><strong>a</strong></a>"
+        );
+        assert!(parse("[a](javascript:alert(1))").is_err());
+    }
+
+    #[test]
+    fn unmatched_delimiters_parse() {
+        let inputs = [
+            "**_||*".repeat(2000),
+            format!("{}{}_", "_*||**".repeat(200), "x".repeat(2000)),
+            "*_|".repeat(5000),
+            "[*](https://a) _".repeat(1000),
+        ];
+        for input in inputs {
+            assert!(parse(&input).is_ok());
+        }
+    }
+}
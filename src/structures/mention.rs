@@ -0,0 +1,91 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::database;
+
+use super::{
+    channel::Channel,
+    markdown::{self, Node},
+    role::Permission,
+};
+
+/// Mentions found in message content.
+///
+/// Users are written as `<@id>`, channels as `<#id>` and everyone in the channel as `@everyone`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Schema)]
+pub struct Mentions {
+    pub users: Vec<String>,
+    pub channels: Vec<String>,
+    pub everyone: bool,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct MentionFetch {
+    pub before: Option<String>,
+    pub max: Option<i64>,
+}
+
+impl Mentions {
+    /// Mentions inside code are ignored.
+    pub fn parse(content: &str) -> Self {
+        let mut mentions = Self::default();
+        if let Ok(nodes) = markdown::parse(content) {
+            mentions.collect(&nodes);
+        }
+        mentions
+    }
+
+    fn collect(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            match node {
+                Node::UserMention { id } if !self.users.contains(id) => self.users.push(id.clone()),
+                Node::ChannelMention { id } if !self.channels.contains(id) => {
+                    self.channels.push(id.clone());
+                }
+                Node::Everyone => self.everyone = true,
+                Node::Bold { children }
+                | Node::Italic { children }
+                | Node::Spoiler { children }
+                | Node::Link { children, .. } => self.collect(children),
+                _ => {}
+            }
+        }
+    }
+
+    /// Drops users that are not in `members` and channels that `author` cannot see, and
+    /// `@everyone` unless `author` may mention everyone in `channel`.
+    pub async fn resolve(mut self, author: &str, members: &[String], channel: &Channel) -> Self {
+        self.users.retain(|it| members.contains(it));
+
+        if self.everyone {
+            self.everyone = channel
+                .get_permissions(author)
+                .await
+                .contains(&Permission::MentionEveryone);
+        }
+
+        let mut channels = vec![];
+        for id in self.channels {
+            let channel = database().await.fetch_channel(id.clone()).await;
+            let Some(channel) = channel.unwrap_or(None) else {
+                continue;
+            };
+            if channel.can_view(author).await {
+                channels.push(id);
+            }
+        }
+        self.channels = channels;
+
+        self
+    }
+
+    /// The users that should be notified, never including the author.
+    pub fn recipients(&self, author: &str, members: &[String]) -> Vec<String> {
+        let users = if self.everyone { members } else { &self.users };
+        users.iter().filter(|it| *it != author).cloned().collect()
+    }
+}
@@ -2,15 +2,29 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use mongodb::error::Error;
 use rweb::Schema;
 use serde::{Deserialize, Serialize};
 use ulid::Ulid;
 
-use crate::database;
+use crate::{database, with_lock};
 
-use super::{channel::Channel, user::User};
+use super::{
+    attachment::Attachment,
+    channel::{Channel, ChannelResponse, ThreadMetadata},
+    client::ClientHolder,
+    component::ActionRow,
+    embed::Embed,
+    encryption::EncryptedContent,
+    event::Event,
+    interaction::MessageInteraction,
+    markdown::{self, RenderFormat, RenderedContent},
+    mention::Mentions,
+    poll::{Poll, PollCreate, PollTally},
+    reaction::ReactionCount,
+    user::User,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone, Schema)]
 pub struct Message {
@@ -19,12 +33,45 @@ pub struct Message {
     pub author_id: Option<String>,
     pub content: String,
     pub created: String,
+    pub reply_to: Option<String>,
+    pub mentions: Mentions,
+    pub attachments: Vec<Attachment>,
+    pub embeds: Vec<Embed>,
+    pub pinned_at: Option<String>,
+    /// The nonce the author sent the message with, to reconcile it with a pending one.
+    pub nonce: Option<String>,
+    /// When the message is deleted, if it is ephemeral.
+    pub expires: Option<String>,
+    pub poll: Option<Poll>,
+    /// Set on responses of bots to interactions.
+    pub interaction: Option<MessageInteraction>,
+    /// Buttons and select menus, on messages sent by bots.
+    pub components: Vec<ActionRow>,
+    /// Set in encrypted channels, where `content` is always empty.
+    pub encrypted: Option<EncryptedContent>,
 }
 
 #[derive(Debug, Deserialize, Schema)]
 pub struct MessageCreate {
     pub channel_id: String,
     pub content: String,
+    pub reply_to: Option<String>,
+    /// Ids of attachments uploaded to `/attachments` by the author.
+    #[serde(default)]
+    pub attachments: Vec<String>,
+    /// Retrying with the same nonce returns the original message instead of sending a new one.
+    pub nonce: Option<String>,
+    /// Seconds after which the message deletes itself.
+    pub expires_in: Option<u64>,
+    pub poll: Option<PollCreate>,
+    /// Structured embeds, which only bots may send.
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    /// Buttons and select menus, which only bots may send.
+    #[serde(default)]
+    pub components: Vec<ActionRow>,
+    /// Required in encrypted channels, instead of `content`.
+    pub encrypted: Option<EncryptedContent>,
 }
 
 #[derive(Debug, Clone, Serialize, Schema)]
@@ -32,6 +79,38 @@ pub struct MessageResponse {
     pub message: Message,
     pub channel: Channel,
     pub author: Option<User>,
+    pub reply: Option<Box<MessageReply>>,
+    pub reactions: Vec<ReactionCount>,
+    pub poll: Option<PollTally>,
+    pub rendered: Option<RenderedContent>,
+}
+
+/// A trimmed copy of the message being replied to.
+///
+/// `message` and `author` are `None` if the referenced message has been deleted.
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct MessageReply {
+    pub id: String,
+    pub message: Option<Message>,
+    pub author: Option<User>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct MessageBulkDelete {
+    pub channel_id: String,
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct MessageDeleteResponse {
+    pub id: String,
+    pub channel_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct MessageDeleteBulkResponse {
+    pub ids: Vec<String>,
+    pub channel_id: String,
 }
 
 #[derive(Debug, Deserialize, Schema)]
@@ -39,6 +118,7 @@ pub struct MessageFetchBefore {
     pub channel: String,
     pub before: String,
     pub max: Option<i64>,
+    pub render: Option<RenderFormat>,
 }
 
 #[derive(Debug, Deserialize, Schema)]
@@ -46,6 +126,24 @@ pub struct MessageFetchAfter {
     pub channel: String,
     pub after: String,
     pub max: Option<i64>,
+    pub render: Option<RenderFormat>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct MessageFetchSingle {
+    pub render: Option<RenderFormat>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct MessageSearch {
+    pub query: String,
+    pub channel: Option<String>,
+    pub guild: Option<String>,
+    pub author: Option<String>,
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub has_attachment: Option<bool>,
+    pub max: Option<i64>,
 }
 
 impl Message {
@@ -56,25 +154,249 @@ impl Message {
             author_id: Some(author_id),
             content,
             created: Utc::now().to_rfc3339(),
+            reply_to: None,
+            mentions: Mentions::default(),
+            attachments: vec![],
+            embeds: vec![],
+            pinned_at: None,
+            nonce: None,
+            expires: None,
+            poll: None,
+            interaction: None,
+            components: vec![],
+            encrypted: None,
         }
     }
 
+    /// Backdates the message, for imported history.
+    ///
+    /// The id is generated from the same time, so ids keep sorting by creation.
+    pub fn with_created(mut self, created: DateTime<Utc>) -> Self {
+        self.id = Ulid::from_datetime(created.into()).to_string();
+        self.created = created.to_rfc3339();
+        self
+    }
+
+    pub fn with_reply_to(mut self, reply_to: Option<String>) -> Self {
+        self.reply_to = reply_to;
+        self
+    }
+
+    pub fn with_mentions(mut self, mentions: Mentions) -> Self {
+        self.mentions = mentions;
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: Option<String>) -> Self {
+        self.nonce = nonce;
+        self
+    }
+
+    pub fn with_expiry(mut self, expires_in: Option<u64>) -> Self {
+        self.expires = expires_in.map(|it| {
+            (Utc::now() + chrono::Duration::seconds(it as i64)).to_rfc3339()
+        });
+        self
+    }
+
+    pub fn with_poll(mut self, poll: Option<Poll>) -> Self {
+        self.poll = poll;
+        self
+    }
+
+    pub fn with_interaction(mut self, interaction: Option<MessageInteraction>) -> Self {
+        self.interaction = interaction;
+        self
+    }
+
+    pub fn with_embeds(mut self, embeds: Vec<Embed>) -> Self {
+        self.embeds = embeds;
+        self
+    }
+
+    pub fn with_components(mut self, components: Vec<ActionRow>) -> Self {
+        self.components = components;
+        self
+    }
+
+    pub fn with_encrypted(mut self, encrypted: Option<EncryptedContent>) -> Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    pub fn with_attachments(mut self, attachments: Vec<Attachment>) -> Self {
+        self.attachments = attachments;
+        self
+    }
+
     pub async fn insert(self) -> Result<Self, Error> {
         database().await.create_message(self).await
     }
+
+    pub async fn delete(&self) -> Result<bool, Error> {
+        let deleted = database().await.delete_message(self.id.clone()).await?;
+        Attachment::remove_all(&self.attachments).await;
+        Ok(deleted)
+    }
+
+    /// Pins or unpins the message, returning the updated message.
+    pub async fn set_pinned(&self, pinned: bool) -> Result<Option<Self>, Error> {
+        let pinned_at = pinned.then(|| Utc::now().to_rfc3339());
+        database()
+            .await
+            .set_message_pinned(self.id.clone(), pinned_at)
+            .await
+    }
+
+    pub async fn fetch_author(&self) -> Option<User> {
+        let user_id = self.author_id.as_ref()?;
+        database().await.fetch_user(user_id).await.unwrap_or(None)
+    }
+
+    fn trimmed(mut self) -> Self {
+        if self.content.chars().count() > REPLY_PREVIEW_LENGTH {
+            self.content = self.content.chars().take(REPLY_PREVIEW_LENGTH).collect();
+        }
+        self.reply_to = None;
+        self
+    }
+}
+
+const REPLY_PREVIEW_LENGTH: usize = 100;
+
+impl MessageReply {
+    pub async fn fetch(id: String) -> Self {
+        let message = database().await.fetch_message(id.clone()).await;
+
+        let Some(message) = message.unwrap_or(None) else {
+            return Self {
+                id,
+                message: None,
+                author: None,
+            };
+        };
+
+        let author = message.fetch_author().await;
+
+        Self {
+            id,
+            message: Some(message.trimmed()),
+            author,
+        }
+    }
 }
 
 impl MessageResponse {
     pub async fn from_message(message: Message, channel: Channel) -> Self {
-        let Some(user_id) = &message.author_id else {
-            return Self::none(message, channel);
-        };
+        let author = message.fetch_author().await;
 
-        let Some(user) = database().await.fetch_user(user_id).await.unwrap_or(None) else {
-            return Self::none(message, channel);
-        };
+        Self::from(message, channel, author)
+            .with_reply()
+            .await
+            .with_reactions()
+            .await
+            .with_poll()
+            .await
+    }
+
+    /// Builds the responses for a page of messages, counting their reactions all at once.
+    pub async fn from_messages(messages: Vec<Message>, channel: &Channel) -> Vec<Self> {
+        let ids = messages.iter().map(|it| it.id.clone()).collect();
+        let mut reactions = database()
+            .await
+            .fetch_reaction_counts(ids)
+            .await
+            .unwrap_or_default();
+
+        let mut out = vec![];
+        for message in messages {
+            let author = message.fetch_author().await;
+            let mut resp = Self::from(message, channel.clone(), author)
+                .with_reply()
+                .await
+                .with_poll()
+                .await;
+            resp.reactions = reactions.remove(&resp.message.id).unwrap_or(vec![]);
+            out.push(resp);
+        }
+        out
+    }
+
+    /// Records the mentions of a newly inserted message, then dispatches it to the channel.
+    ///
+    /// Threads are kept active, and are unarchived if needed.
+    pub async fn publish(
+        message: Message,
+        channel: Channel,
+        author: User,
+        users: Vec<String>,
+        clients: &ClientHolder,
+    ) -> Result<Self, Error> {
+        let recipients = message.mentions.recipients(&author.id, &users);
+
+        database()
+            .await
+            .create_mentions(&message, &recipients)
+            .await?;
+
+        let mut thread = None;
+        if let Some(metadata) = &channel.thread {
+            let archive_at = ThreadMetadata::archive_at(metadata.auto_archive_after);
+            let touched = database()
+                .await
+                .touch_thread(channel.id.clone(), archive_at)
+                .await?;
+            if metadata.archived {
+                thread = touched.map(ChannelResponse::from_channel);
+            }
+        }
+
+        let resp = Self::from(message, channel, Some(author))
+            .with_reply()
+            .await
+            .with_poll()
+            .await;
+
+        {
+            let lock = with_lock!(clients);
+            if let Some(thread) = thread {
+                lock.dispatch_users(users.clone(), &Event::ThreadUpdate(thread));
+            }
+            lock.dispatch_users(users, &Event::MessageCreate(resp.clone()));
+            lock.dispatch_users(recipients, &Event::MentionCreate(resp.clone()));
+        }
+
+        Embed::resolve_in_background(resp.message.clone(), clients.clone());
+
+        Ok(resp)
+    }
+
+    pub async fn with_reply(mut self) -> Self {
+        if let Some(id) = &self.message.reply_to {
+            self.reply = Some(Box::new(MessageReply::fetch(id.clone()).await));
+        }
+        self
+    }
+
+    pub async fn with_poll(mut self) -> Self {
+        self.poll = PollTally::fetch(&self.message).await.unwrap_or(None);
+        self
+    }
+
+    pub fn with_render(mut self, format: Option<RenderFormat>) -> Self {
+        self.rendered = format.and_then(|it| markdown::render(&self.message.content, it));
+        self
+    }
 
-        Self::from(message, channel, Some(user))
+    pub async fn with_reactions(mut self) -> Self {
+        self.reactions = database()
+            .await
+            .fetch_reaction_counts(vec![self.message.id.clone()])
+            .await
+            .ok()
+            .and_then(|mut it| it.remove(&self.message.id))
+            .unwrap_or(vec![]);
+        self
     }
 
     pub fn none(message: Message, channel: Channel) -> Self {
@@ -86,6 +408,10 @@ impl MessageResponse {
             message,
             channel,
             author,
+            reply: None,
+            reactions: vec![],
+            poll: None,
+            rendered: None,
         }
     }
 }
@@ -4,18 +4,35 @@
 
 use reqwest::StatusCode;
 use rweb::{Schema, openapi::{ResponseEntity, Entity}};
+use serde::Serialize;
 use warp::{reply::Response, Reply};
 
+pub mod attachment;
 pub mod auth;
 pub mod channel;
 pub mod client;
+pub mod command;
+pub mod component;
+pub mod embed;
+pub mod emoji;
+pub mod encryption;
 pub mod error;
 pub mod event;
 pub mod guild;
+pub mod interaction;
+pub mod markdown;
+pub mod mention;
 pub mod message;
+pub mod poll;
+pub mod reaction;
+pub mod read_state;
 pub mod response;
 pub mod restricted_string;
+pub mod role;
+pub mod scheduled_message;
+pub mod transcript;
 pub mod user;
+pub mod validation;
 
 #[derive(Debug, Schema)]
 pub struct WithStatus<T: Entity> {
@@ -35,6 +52,19 @@ pub fn with_status<T: Entity>(data: T, status: StatusCode) -> WithStatus<T> {
     WithStatus { reply: data, status: status.as_u16() }
 }
 
+impl<T: Entity + Serialize> WithStatus<response::Response<T>> {
+    /// Takes the data out of a successful response, or gives back an error response.
+    pub fn success(self) -> Result<T, Self> {
+        match self.reply {
+            response::Response::Success { data } => Ok(data),
+            reply => Err(Self {
+                reply,
+                status: self.status,
+            }),
+        }
+    }
+}
+
 impl <T: Entity + ResponseEntity> ResponseEntity for WithStatus<T> {
     fn describe_responses(comp_d: &mut rweb::openapi::ComponentDescriptor) -> rweb::openapi::Responses {
         T::describe_responses(comp_d)
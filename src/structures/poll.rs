@@ -0,0 +1,140 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use chrono::{DateTime, Duration, Utc};
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+
+use crate::database;
+
+use super::{message::Message, validation};
+
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+const MAX_TEXT_LENGTH: usize = 300;
+const MAX_DURATION_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Poll {
+    pub question: String,
+    pub options: Vec<String>,
+    pub multi_select: bool,
+    pub closes_at: String,
+    /// The final vote count of each option, set once the poll has closed.
+    pub results: Option<Vec<u64>>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct PollCreate {
+    pub question: String,
+    pub options: Vec<String>,
+    #[serde(default)]
+    pub multi_select: bool,
+    pub closes_at: String,
+}
+
+/// The vote count of each option of a poll.
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct PollTally {
+    pub message_id: String,
+    pub channel_id: String,
+    pub counts: Vec<u64>,
+    pub closed: bool,
+}
+
+#[derive(Debug, Schema)]
+pub enum PollError {
+    EmptyText,
+    TextTooLong,
+    OptionCount,
+    InvalidClosingTime,
+}
+
+impl PollCreate {
+    pub fn validate(&self) -> Result<Poll, PollError> {
+        if !(MIN_OPTIONS..=MAX_OPTIONS).contains(&self.options.len()) {
+            return Err(PollError::OptionCount);
+        }
+
+        for text in self.options.iter().chain([&self.question]) {
+            if text.trim().is_empty() {
+                return Err(PollError::EmptyText);
+            }
+            if validation::exceeds(text, MAX_TEXT_LENGTH) {
+                return Err(PollError::TextTooLong);
+            }
+        }
+
+        let Ok(closes_at) = DateTime::parse_from_rfc3339(&self.closes_at) else {
+            return Err(PollError::InvalidClosingTime);
+        };
+        let closes_at = closes_at.with_timezone(&Utc);
+        let now = Utc::now();
+        if closes_at <= now || closes_at > now + Duration::days(MAX_DURATION_DAYS) {
+            return Err(PollError::InvalidClosingTime);
+        }
+
+        Ok(Poll {
+            question: self.question.clone(),
+            options: self.options.clone(),
+            multi_select: self.multi_select,
+            closes_at: closes_at.to_rfc3339(),
+            results: None,
+        })
+    }
+}
+
+impl Poll {
+    pub fn is_closed(&self) -> bool {
+        self.results.is_some()
+            || DateTime::parse_from_rfc3339(&self.closes_at).map_or(true, |it| it <= Utc::now())
+    }
+}
+
+impl PollTally {
+    /// Tallies the votes of a poll message, or returns the final results if it has closed.
+    pub async fn fetch(message: &Message) -> Result<Option<Self>, Error> {
+        let Some(poll) = &message.poll else {
+            return Ok(None);
+        };
+
+        let counts = match &poll.results {
+            Some(results) => results.clone(),
+            None => {
+                database()
+                    .await
+                    .count_poll_votes(message.id.clone(), poll.options.len())
+                    .await?
+            }
+        };
+
+        Ok(Some(Self {
+            message_id: message.id.clone(),
+            channel_id: message.channel_id.clone(),
+            counts,
+            closed: poll.is_closed(),
+        }))
+    }
+}
+
+impl Display for PollError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyText => write!(f, "Poll questions and options must not be empty"),
+            Self::TextTooLong => write!(
+                f,
+                "Poll questions and options may be at most {MAX_TEXT_LENGTH} characters"
+            ),
+            Self::OptionCount => {
+                write!(f, "Polls must have {MIN_OPTIONS} to {MAX_OPTIONS} options")
+            }
+            Self::InvalidClosingTime => write!(
+                f,
+                "Polls must close within {MAX_DURATION_DAYS} days from now"
+            ),
+        }
+    }
+}
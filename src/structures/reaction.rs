@@ -0,0 +1,72 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Error;
+use percent_encoding::percent_decode_str;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::database;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Reaction {
+    pub message_id: String,
+    pub emoji: String,
+    pub user_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct ReactionResponse {
+    pub reaction: Reaction,
+    pub channel_id: String,
+}
+
+const MAX_EMOJI_LENGTH: usize = 64;
+
+impl Reaction {
+    pub fn new(message_id: String, emoji: String, user_id: String) -> Self {
+        Self {
+            message_id,
+            emoji,
+            user_id,
+        }
+    }
+
+    /// Decodes an emoji taken from a request path.
+    ///
+    /// Returns `None` if the emoji is empty, too long or contains whitespace.
+    pub fn decode_emoji(raw: &str) -> Option<String> {
+        let emoji = percent_decode_str(raw).decode_utf8().ok()?.to_string();
+        if emoji.is_empty() || emoji.len() > MAX_EMOJI_LENGTH || emoji.contains(char::is_whitespace)
+        {
+            return None;
+        }
+        Some(emoji)
+    }
+
+    /// Returns `false` if the user had already reacted with this emoji.
+    pub async fn insert(&self) -> Result<bool, Error> {
+        database().await.create_reaction(self).await
+    }
+
+    /// Returns `false` if there was no such reaction.
+    pub async fn delete(&self) -> Result<bool, Error> {
+        database().await.delete_reaction(self).await
+    }
+}
+
+impl ReactionResponse {
+    pub fn new(reaction: Reaction, channel_id: String) -> Self {
+        Self {
+            reaction,
+            channel_id,
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::database;
+
+use super::channel::Channel;
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct ReadState {
+    pub channel_id: String,
+    /// `None` if the user has never read the channel.
+    pub last_message_id: Option<String>,
+    pub unread_count: u64,
+    pub mention_count: u64,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct ReadStateAck {
+    pub message_id: String,
+}
+
+#[derive(Debug)]
+pub enum AckError {
+    UnknownMessage,
+    /// The channel is already read up to this message or a later one.
+    Behind,
+}
+
+impl ReadState {
+    /// Marks a channel as read up to a message. Read states only ever move forward.
+    pub async fn ack(
+        user_id: &str,
+        channel: &Channel,
+        message_id: String,
+    ) -> Result<Result<Self, AckError>, Error> {
+        let database = database().await;
+
+        let Some(message) = database.fetch_message(message_id).await? else {
+            return Ok(Err(AckError::UnknownMessage));
+        };
+        if message.channel_id != channel.id {
+            return Ok(Err(AckError::UnknownMessage));
+        }
+
+        if !database.set_read_state(user_id, &message).await? {
+            return Ok(Err(AckError::Behind));
+        }
+
+        Ok(Ok(database.fetch_read_state(user_id, &channel.id).await?))
+    }
+}
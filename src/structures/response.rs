@@ -35,9 +35,14 @@ where
                 status_code,
                 message,
             } => {
-                let mut err = serializer.serialize_struct("Error", 2)?;
+                let details = message.details();
+                let len = if details.is_some() { 3 } else { 2 };
+                let mut err = serializer.serialize_struct("Error", len)?;
                 err.serialize_field("status_code", status_code)?;
                 err.serialize_field("message", message)?;
+                if let Some(details) = details {
+                    err.serialize_field("details", details)?;
+                }
                 err.end()
             }
             Self::Success { data } => data.serialize(serializer),
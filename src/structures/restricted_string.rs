@@ -4,21 +4,23 @@
 
 use regex::Regex;
 
-/// A string that only contains a subset of characters and <= 32 chars.
+use super::validation::{self, Field};
+
+/// A string that only contains a subset of characters and fits the limit of its field.
 ///
 /// Illegal characters are replaced with '-' or ' '.
 ///
-/// Inputs > 32 are trimmed to be 32 in length.
+/// Inputs over the limit are trimmed to the limit, counting grapheme clusters.
 #[derive(Debug, Clone, Copy)]
 pub struct RestrictedString;
 
 impl RestrictedString {
-    pub fn space(value: &str) -> String {
-        Self::trim(Self::filter(value, " "))
+    pub fn space(value: &str, field: Field) -> String {
+        Self::trim(Self::filter(value, " "), field)
     }
 
-    pub fn no_space(value: &str) -> String {
-        Self::trim(Self::filter(value, "-"))
+    pub fn no_space(value: &str, field: Field) -> String {
+        Self::trim(Self::filter(value, "-"), field)
     }
 
     fn filter(value: &str, repl: &str) -> String {
@@ -28,10 +30,7 @@ impl RestrictedString {
             .to_string()
     }
 
-    fn trim(value: String) -> String {
-        if value.len() <= 32 {
-            return value;
-        }
-        value.split_at(32).0.to_string()
+    fn trim(value: String, field: Field) -> String {
+        validation::truncate(&value, field.max_length())
     }
 }
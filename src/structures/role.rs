@@ -0,0 +1,364 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Guild roles, and the permission overwrites of guild channels.
+//!
+//! Members may do everything in a channel but mention everyone, unless an overwrite denies it.
+//! Overwrites apply in order: the one of the implicit everyone role, whose id is the guild id,
+//! then those of the member's roles, where allows win over denies, then the one of the member.
+//! Threads follow the overwrites of their parent, and the guild owner is never restricted.
+
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{database, generate_ulid};
+
+use super::{restricted_string::RestrictedString, user::User, validation::Field};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct Role {
+    pub id: String,
+    pub guild_id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct RoleCreate {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct GuildRolesResponse {
+    pub guild_id: String,
+    pub roles: Vec<Role>,
+}
+
+/// A member of a guild, with the ids of their roles in it.
+#[derive(Debug, Clone, Serialize, Schema)]
+pub struct GuildMember {
+    pub guild_id: String,
+    pub user: User,
+    pub roles: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum Permission {
+    /// Seeing the channel, its messages and its events. Without it, no other permission applies.
+    #[serde(rename = "view_channel")]
+    ViewChannel,
+    #[serde(rename = "send_messages")]
+    SendMessages,
+    /// Notifying every member of the channel with `@everyone`. Only granted by overwrites.
+    #[serde(rename = "mention_everyone")]
+    MentionEveryone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum OverwriteKind {
+    #[serde(rename = "role")]
+    Role,
+    #[serde(rename = "member")]
+    Member,
+}
+
+/// Allows or denies permissions in a channel to the members of a role, or to a single member.
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct PermissionOverwrite {
+    /// The id of the role or member. The guild id stands for every member.
+    pub id: String,
+    pub kind: OverwriteKind,
+    #[serde(default)]
+    pub allow: Vec<Permission>,
+    #[serde(default)]
+    pub deny: Vec<Permission>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct OverwriteUpdate {
+    pub kind: OverwriteKind,
+    #[serde(default)]
+    pub allow: Vec<Permission>,
+    #[serde(default)]
+    pub deny: Vec<Permission>,
+}
+
+/// A guild with its owner and members, to resolve permissions in many of its channels
+/// without fetching them again.
+#[derive(Debug, Clone)]
+pub struct GuildPermissions {
+    pub guild_id: String,
+    owner_id: String,
+    members: Vec<GuildMember>,
+}
+
+impl Permission {
+    pub const ALL: [Self; 3] = [Self::ViewChannel, Self::SendMessages, Self::MentionEveryone];
+
+    /// What members may do without overwrites.
+    pub const DEFAULT: [Self; 2] = [Self::ViewChannel, Self::SendMessages];
+}
+
+impl Role {
+    pub fn new(guild_id: &str, name: &str) -> Self {
+        Self {
+            id: generate_ulid(),
+            guild_id: guild_id.to_string(),
+            name: RestrictedString::space(name, Field::RoleName),
+        }
+    }
+
+    pub async fn insert(self) -> Result<Self, Error> {
+        database().await.create_role(self).await
+    }
+}
+
+impl GuildPermissions {
+    /// Fetches a guild with all of its members.
+    pub async fn fetch(guild_id: &str) -> Result<Option<Self>, Error> {
+        let database = database().await;
+        let Some(guild) = database.fetch_guild(guild_id).await? else {
+            return Ok(None)
+        };
+        let members = database.fetch_members(&guild.id).await?;
+        Ok(Some(Self {
+            guild_id: guild.id,
+            owner_id: guild.owner_id,
+            members,
+        }))
+    }
+
+    /// Fetches a guild with only one of its members, who may not be in it.
+    pub async fn fetch_member(guild_id: &str, user_id: &str) -> Result<Option<Self>, Error> {
+        let database = database().await;
+        let Some(guild) = database.fetch_guild(guild_id).await? else {
+            return Ok(None)
+        };
+        let member = database.fetch_guild_member(&guild.id, user_id).await?;
+        Ok(Some(Self {
+            guild_id: guild.id,
+            owner_id: guild.owner_id,
+            members: member.into_iter().collect(),
+        }))
+    }
+
+    pub fn is_member(&self, user_id: &str) -> bool {
+        self.members.iter().any(|it| it.user.id == user_id)
+    }
+
+    /// The permissions of a user in a channel with the overwrites.
+    ///
+    /// The owner has them all, and users outside the guild none.
+    pub fn resolve(&self, overwrites: &[PermissionOverwrite], user_id: &str) -> Vec<Permission> {
+        let Some(member) = self.members.iter().find(|it| it.user.id == user_id) else {
+            return vec![]
+        };
+        self.resolve_member(overwrites, member)
+    }
+
+    /// The members that can see a channel with the overwrites.
+    pub fn viewers(&self, overwrites: &[PermissionOverwrite]) -> Vec<String> {
+        self.members
+            .iter()
+            .filter(|it| {
+                self.resolve_member(overwrites, it)
+                    .contains(&Permission::ViewChannel)
+            })
+            .map(|it| it.user.id.clone())
+            .collect()
+    }
+
+    fn resolve_member(
+        &self,
+        overwrites: &[PermissionOverwrite],
+        member: &GuildMember,
+    ) -> Vec<Permission> {
+        if member.user.id == self.owner_id {
+            return Permission::ALL.to_vec();
+        }
+        PermissionOverwrite::resolve(overwrites, &self.guild_id, &member.user.id, &member.roles)
+    }
+}
+
+impl PermissionOverwrite {
+    /// The permissions a member with the given roles has under the overwrites of a channel.
+    pub fn resolve(
+        overwrites: &[Self],
+        guild_id: &str,
+        user_id: &str,
+        roles: &[String],
+    ) -> Vec<Permission> {
+        let mut permissions = Permission::DEFAULT.to_vec();
+
+        let everyone = overwrites
+            .iter()
+            .find(|it| it.kind == OverwriteKind::Role && it.id == guild_id);
+        if let Some(everyone) = everyone {
+            everyone.apply(&mut permissions);
+        }
+
+        let role_overwrites: Vec<_> = overwrites
+            .iter()
+            .filter(|it| it.kind == OverwriteKind::Role && roles.contains(&it.id))
+            .collect();
+        for overwrite in &role_overwrites {
+            permissions.retain(|it| !overwrite.deny.contains(it));
+        }
+        for overwrite in &role_overwrites {
+            grant(&mut permissions, &overwrite.allow);
+        }
+
+        let member = overwrites
+            .iter()
+            .find(|it| it.kind == OverwriteKind::Member && it.id == user_id);
+        if let Some(member) = member {
+            member.apply(&mut permissions);
+        }
+
+        if !permissions.contains(&Permission::ViewChannel) {
+            permissions.clear();
+        }
+        permissions
+    }
+
+    fn apply(&self, permissions: &mut Vec<Permission>) {
+        permissions.retain(|it| !self.deny.contains(it));
+        grant(permissions, &self.allow);
+    }
+}
+
+fn grant(permissions: &mut Vec<Permission>, allow: &[Permission]) {
+    for permission in allow {
+        if !permissions.contains(permission) {
+            permissions.push(*permission);
+        }
+    }
+}
+
+impl OverwriteUpdate {
+    /// Whether no permission is both allowed and denied.
+    pub fn is_valid(&self) -> bool {
+        !self.allow.iter().any(|it| self.deny.contains(it))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use OverwriteKind::{Member, Role};
+    use Permission::{MentionEveryone as Everyone, SendMessages as Send, ViewChannel as View};
+
+    const GUILD: &str = "guild";
+    const USER: &str = "user";
+
+    fn overwrite(
+        id: &str,
+        kind: OverwriteKind,
+        allow: &[Permission],
+        deny: &[Permission],
+    ) -> PermissionOverwrite {
+        PermissionOverwrite {
+            id: id.to_string(),
+            kind,
+            allow: allow.to_vec(),
+            deny: deny.to_vec(),
+        }
+    }
+
+    fn resolve(overwrites: &[PermissionOverwrite], roles: &[&str]) -> Vec<Permission> {
+        let roles: Vec<String> = roles.iter().map(|it| it.to_string()).collect();
+        let mut permissions = PermissionOverwrite::resolve(overwrites, GUILD, USER, &roles);
+        permissions.sort_by_key(|it| *it as u8);
+        permissions
+    }
+
+    #[test]
+    fn everything_is_allowed_without_overwrites() {
+        assert_eq!(resolve(&[], &[]), [View, Send]);
+    }
+
+    #[test]
+    fn everyone_role_applies_to_all_members() {
+        let overwrites = [overwrite(GUILD, Role, &[], &[Send])];
+        assert_eq!(resolve(&overwrites, &["a"]), [View]);
+    }
+
+    #[test]
+    fn role_allow_wins_over_role_deny() {
+        let overwrites = [
+            overwrite(GUILD, Role, &[], &[View]),
+            overwrite("a", Role, &[], &[Send]),
+            overwrite("b", Role, &[View, Send], &[]),
+        ];
+        assert_eq!(resolve(&overwrites, &[]), []);
+        assert_eq!(resolve(&overwrites, &["a"]), []);
+        assert_eq!(resolve(&overwrites, &["a", "b"]), [View, Send]);
+        assert_eq!(resolve(&overwrites, &["b", "a"]), [View, Send]);
+    }
+
+    #[test]
+    fn member_overwrite_wins_over_roles() {
+        let overwrites = [
+            overwrite("a", Role, &[Send], &[]),
+            overwrite(USER, Member, &[], &[Send]),
+        ];
+        assert_eq!(resolve(&overwrites, &["a"]), [View]);
+
+        let overwrites = [
+            overwrite(GUILD, Role, &[], &[View]),
+            overwrite("a", Role, &[], &[View]),
+            overwrite(USER, Member, &[View], &[]),
+        ];
+        assert_eq!(resolve(&overwrites, &["a"]), [View, Send]);
+    }
+
+    #[test]
+    fn overwrites_of_others_do_not_apply() {
+        let overwrites = [
+            overwrite("a", Role, &[], &[View]),
+            overwrite("other", Member, &[], &[View]),
+        ];
+        assert_eq!(resolve(&overwrites, &["b"]), [View, Send]);
+    }
+
+    #[test]
+    fn mention_everyone_must_be_allowed() {
+        assert!(!resolve(&[], &[]).contains(&Everyone));
+
+        let overwrites = [overwrite("a", Role, &[Everyone], &[])];
+        assert_eq!(resolve(&overwrites, &["a"]), [View, Send, Everyone]);
+        assert_eq!(resolve(&overwrites, &["b"]), [View, Send]);
+    }
+
+    #[test]
+    fn nothing_is_allowed_without_view() {
+        let overwrites = [overwrite(GUILD, Role, &[Send], &[View])];
+        assert_eq!(resolve(&overwrites, &[]), []);
+    }
+
+    #[test]
+    fn owner_is_never_restricted() {
+        let member = |id: &str| GuildMember {
+            guild_id: GUILD.to_string(),
+            user: User {
+                id: id.to_string(),
+                username: id.to_string(),
+                discriminator: 1,
+                bot: false,
+            },
+            roles: vec![],
+        };
+        let permissions = GuildPermissions {
+            guild_id: GUILD.to_string(),
+            owner_id: "owner".to_string(),
+            members: vec![member("owner"), member(USER)],
+        };
+        let overwrites = [overwrite(GUILD, Role, &[], &[View])];
+
+        assert_eq!(permissions.viewers(&overwrites), ["owner"]);
+        assert_eq!(permissions.resolve(&overwrites, "owner"), Permission::ALL);
+        assert!(permissions.resolve(&[], "stranger").is_empty());
+    }
+}
@@ -0,0 +1,149 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use chrono::{DateTime, Datelike, Duration, Utc, Weekday};
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::{database, generate_ulid};
+
+#[derive(Debug, Clone, Serialize, Deserialize, Schema)]
+pub struct ScheduledMessage {
+    pub id: String,
+    pub channel_id: String,
+    pub author_id: String,
+    pub content: String,
+    pub send_at: String,
+    pub repeat: Option<RepeatRule>,
+}
+
+/// How often a scheduled message is sent again, in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Schema)]
+pub enum RepeatRule {
+    #[serde(rename = "hourly")]
+    Hourly,
+    #[serde(rename = "daily")]
+    Daily,
+    /// Daily, skipping Saturday and Sunday.
+    #[serde(rename = "weekdays")]
+    Weekdays,
+    #[serde(rename = "weekly")]
+    Weekly,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct ScheduledMessageCreate {
+    pub channel_id: String,
+    pub content: String,
+    pub send_at: String,
+    pub repeat: Option<RepeatRule>,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct ScheduledMessageFetch {
+    pub channel: Option<String>,
+}
+
+impl ScheduledMessage {
+    pub fn new(
+        channel_id: String,
+        author_id: String,
+        content: String,
+        send_at: DateTime<Utc>,
+        repeat: Option<RepeatRule>,
+    ) -> Self {
+        Self {
+            id: generate_ulid(),
+            channel_id,
+            author_id,
+            content,
+            send_at: send_at.to_rfc3339(),
+            repeat,
+        }
+    }
+
+    pub async fn insert(self) -> Result<Self, Error> {
+        database().await.create_scheduled_message(self).await
+    }
+
+    /// The next time the message should be sent after it was sent at `now`.
+    ///
+    /// Occurrences missed while the server was down are skipped.
+    pub fn next_send_at(&self, now: DateTime<Utc>) -> Option<String> {
+        let rule = self.repeat?;
+        let mut next = DateTime::parse_from_rfc3339(&self.send_at)
+            .ok()?
+            .with_timezone(&Utc);
+        while next <= now {
+            next = rule.next(next);
+        }
+        Some(next.to_rfc3339())
+    }
+}
+
+impl RepeatRule {
+    pub fn next(self, time: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Self::Hourly => time + Duration::hours(1),
+            Self::Daily => time + Duration::days(1),
+            Self::Weekdays => {
+                let mut next = time + Duration::days(1);
+                while matches!(next.weekday(), Weekday::Sat | Weekday::Sun) {
+                    next += Duration::days(1);
+                }
+                next
+            }
+            Self::Weekly => time + Duration::weeks(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 5, day, hour, 30, 0).unwrap()
+    }
+
+    fn scheduled(send_at: DateTime<Utc>, repeat: Option<RepeatRule>) -> ScheduledMessage {
+        ScheduledMessage::new(String::new(), String::new(), String::new(), send_at, repeat)
+    }
+
+    #[test]
+    fn rules_repeat_by_their_period() {
+        assert_eq!(RepeatRule::Hourly.next(at(10, 9)), at(10, 10));
+        assert_eq!(RepeatRule::Daily.next(at(10, 9)), at(11, 9));
+        assert_eq!(RepeatRule::Weekly.next(at(10, 9)), at(17, 9));
+    }
+
+    #[test]
+    fn weekdays_skip_the_weekend() {
+        // The 10th is a Friday.
+        assert_eq!(RepeatRule::Weekdays.next(at(9, 9)), at(10, 9));
+        assert_eq!(RepeatRule::Weekdays.next(at(10, 9)), at(13, 9));
+        assert_eq!(RepeatRule::Weekdays.next(at(11, 9)), at(13, 9));
+    }
+
+    #[test]
+    fn missed_occurrences_are_skipped() {
+        let message = scheduled(at(1, 9), Some(RepeatRule::Daily));
+        assert_eq!(
+            message.next_send_at(at(10, 12)),
+            Some(at(11, 9).to_rfc3339())
+        );
+        assert_eq!(
+            message.next_send_at(at(10, 9)),
+            Some(at(11, 9).to_rfc3339())
+        );
+    }
+
+    #[test]
+    fn messages_without_rule_do_not_repeat() {
+        assert_eq!(scheduled(at(1, 9), None).next_send_at(at(10, 12)), None);
+    }
+}
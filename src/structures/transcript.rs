@@ -0,0 +1,239 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use futures::{stream, Stream};
+use mongodb::error::Error;
+use rweb::Schema;
+use serde::{Deserialize, Serialize};
+
+use crate::database;
+
+use super::{
+    channel::Channel,
+    markdown::{self, RenderFormat},
+    message::Message,
+};
+
+/// Messages are read from the database this many at a time.
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Schema)]
+pub enum TranscriptFormat {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "text")]
+    Text,
+    #[serde(rename = "html")]
+    Html,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct TranscriptQuery {
+    pub format: Option<TranscriptFormat>,
+}
+
+#[derive(Serialize)]
+struct TranscriptEntry<'a> {
+    author: &'a str,
+    message: &'a Message,
+}
+
+/// A transcript of the entire history of a channel, oldest message first.
+pub struct Transcript {
+    channel: Channel,
+    format: TranscriptFormat,
+    /// Author names by user id, so each author is only fetched once.
+    authors: HashMap<String, String>,
+    written: usize,
+}
+
+enum Part {
+    Header,
+    /// The time and id of the last message written, if any.
+    Page { after: Option<(String, String)> },
+    Footer,
+    Done,
+}
+
+impl TranscriptFormat {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json; charset=utf-8",
+            Self::Text => "text/plain; charset=utf-8",
+            Self::Html => "text/html; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Text => "txt",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl Transcript {
+    pub fn new(channel: Channel, format: TranscriptFormat) -> Self {
+        Self {
+            channel,
+            format,
+            authors: HashMap::new(),
+            written: 0,
+        }
+    }
+
+    /// Streams the transcript a page of messages at a time, so only one page is held in memory.
+    ///
+    /// The stream fails if a page can't be read, rather than ending with a partial transcript.
+    pub fn stream(self) -> impl Stream<Item = Result<String, Error>> {
+        stream::unfold((self, Part::Header), |(mut transcript, part)| async move {
+            match part {
+                Part::Header => {
+                    let header = transcript.header();
+                    Some((Ok(header), (transcript, Part::Page { after: None })))
+                }
+                Part::Page { after } => match transcript.page(after).await {
+                    Ok((chunk, Some(after))) => {
+                        let after = Some(after);
+                        Some((Ok(chunk), (transcript, Part::Page { after })))
+                    }
+                    Ok((chunk, None)) => Some((Ok(chunk), (transcript, Part::Footer))),
+                    Err(err) => Some((Err(err), (transcript, Part::Done))),
+                },
+                Part::Footer => {
+                    let footer = transcript.footer();
+                    Some((Ok(footer), (transcript, Part::Done)))
+                }
+                Part::Done => None,
+            }
+        })
+    }
+
+    /// Writes the messages after `after`, returning where the next page starts if there is one.
+    async fn page(
+        &mut self,
+        after: Option<(String, String)>,
+    ) -> Result<(String, Option<(String, String)>), Error> {
+        let after = after.as_ref().map(|(time, id)| (time.as_str(), id.as_str()));
+        let messages = database()
+            .await
+            .fetch_transcript_page(&self.channel.id, after, PAGE_SIZE)
+            .await?;
+
+        let mut out = String::new();
+        for message in &messages {
+            let author = self.author(message).await?;
+            out.push_str(&self.entry(&author, message));
+            self.written += 1;
+        }
+
+        let next = match messages.last() {
+            Some(last) if messages.len() as i64 == PAGE_SIZE => {
+                Some((last.created.clone(), last.id.clone()))
+            }
+            _ => None,
+        };
+
+        Ok((out, next))
+    }
+
+    async fn author(&mut self, message: &Message) -> Result<String, Error> {
+        let Some(id) = &message.author_id else {
+            return Ok("Deleted User".to_string());
+        };
+        if let Some(name) = self.authors.get(id) {
+            return Ok(name.clone());
+        }
+        let name = database()
+            .await
+            .fetch_user(id)
+            .await?
+            .map_or("Deleted User".to_string(), |it| it.to_string());
+        self.authors.insert(id.clone(), name.clone());
+        Ok(name)
+    }
+
+    fn header(&self) -> String {
+        let exported = Utc::now().to_rfc3339();
+        match self.format {
+            TranscriptFormat::Json => format!(
+                "{{\"channel\":{},\"exported\":\"{exported}\",\"messages\":[",
+                serde_json::to_string(&self.channel).unwrap_or("null".to_string())
+            ),
+            TranscriptFormat::Text => format!(
+                "#{} ({})\nExported {exported}\n\n",
+                self.channel.name, self.channel.id
+            ),
+            TranscriptFormat::Html => {
+                let name = markdown::escape(&self.channel.name);
+                format!(
+                    "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>#{name}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>#{name}</h1>\n<p class=\"exported\">Exported {exported}</p>\n"
+                )
+            }
+        }
+    }
+
+    fn entry(&self, author: &str, message: &Message) -> String {
+        match self.format {
+            TranscriptFormat::Json => {
+                let entry = serde_json::to_string(&TranscriptEntry { author, message })
+                    .unwrap_or("null".to_string());
+                if self.written == 0 {
+                    entry
+                } else {
+                    format!(",{entry}")
+                }
+            }
+            TranscriptFormat::Text => {
+                let mut out = format!("[{}] {author}: {}\n", message.created, message.content);
+                for attachment in &message.attachments {
+                    out.push_str(&format!("    Attachment: {}\n", attachment.filename));
+                }
+                out
+            }
+            TranscriptFormat::Html => {
+                let content = markdown::render(&message.content, RenderFormat::Html)
+                    .and_then(|it| it.html)
+                    .unwrap_or(markdown::escape(&message.content));
+                let mut out = format!(
+                    "<div class=\"message\"><span class=\"author\">{}</span> <time>{}</time><div class=\"content\">{content}</div>",
+                    markdown::escape(author),
+                    markdown::escape(&message.created)
+                );
+                for attachment in &message.attachments {
+                    out.push_str(&format!(
+                        "<div class=\"attachment\">{}</div>",
+                        markdown::escape(&attachment.filename)
+                    ));
+                }
+                out.push_str("</div>\n");
+                out
+            }
+        }
+    }
+
+    fn footer(&self) -> String {
+        match self.format {
+            TranscriptFormat::Json => "]}".to_string(),
+            TranscriptFormat::Text => format!("\n{} messages\n", self.written),
+            TranscriptFormat::Html => {
+                format!(
+                    "<p class=\"count\">{} messages</p>\n</body>\n</html>\n",
+                    self.written
+                )
+            }
+        }
+    }
+}
+
+const STYLE: &str = "body{font-family:sans-serif;max-width:60em;margin:auto;padding:1em}\
+.message{margin:.5em 0}.author{font-weight:bold}time,.exported,.count{color:#888;font-size:.8em}\
+.attachment{color:#555;font-style:italic}.spoiler{background:#000}.spoiler:hover{background:none}\
+.mention{background:#e0e7ff}img.emoji{height:1.3em;vertical-align:middle}\
+pre{background:#f4f4f4;padding:.5em;overflow-x:auto}";
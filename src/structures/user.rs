@@ -8,13 +8,21 @@ use serde::{Deserialize, Serialize};
 
 use crate::database;
 
-use super::{auth::Service, restricted_string::RestrictedString};
+use super::{auth::Service, restricted_string::RestrictedString, validation::Field};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Schema)]
 pub struct User {
     pub id: String,
     pub username: String,
     pub discriminator: u32,
+    /// Bots are users created by another user, and log in with their token only.
+    #[serde(default)]
+    pub bot: bool,
+}
+
+#[derive(Debug, Deserialize, Schema)]
+pub struct BotCreate {
+    pub username: String,
 }
 
 #[derive(Debug, Deserialize, Schema)]
@@ -40,7 +48,14 @@ impl User {
     pub async fn create(username: &str) -> Result<Option<(Self, String)>, Error> {
         database()
             .await
-            .create_user(&RestrictedString::space(username))
+            .create_user(&RestrictedString::space(username, Field::Username))
+            .await
+    }
+
+    pub async fn create_bot(username: &str, owner_id: &str) -> Result<Option<(Self, String)>, Error> {
+        database()
+            .await
+            .create_bot(&RestrictedString::space(username, Field::Username), owner_id)
             .await
     }
 }
@@ -0,0 +1,221 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::fmt::Display;
+
+use rweb::Schema;
+use serde::{ser::SerializeStruct, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::restricted_string::RestrictedString;
+
+/// A grapheme cluster may combine any number of code points, so values are also limited to
+/// this many bytes for each grapheme cluster they may have.
+pub const MAX_GRAPHEME_BYTES: usize = 16;
+
+/// A user supplied field with a length limit.
+///
+/// Lengths are counted in grapheme clusters, so an emoji with modifiers counts once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Schema)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Content,
+    ChannelName,
+    GuildName,
+    Username,
+    DeviceName,
+    ChannelTopic,
+    RoleName,
+}
+
+#[derive(Debug, Schema)]
+pub enum ValidationError {
+    Empty { field: Field },
+    TooLong { field: Field, max: usize },
+    TooLarge { field: Field, max_bytes: usize },
+}
+
+impl Field {
+    /// The maximum length of the field, configurable through the environment.
+    pub fn max_length(self) -> usize {
+        let (var, default) = match self {
+            Self::Content => ("MAX_MESSAGE_LENGTH", 4000),
+            Self::ChannelName => ("MAX_CHANNEL_NAME_LENGTH", 32),
+            Self::GuildName => ("MAX_GUILD_NAME_LENGTH", 32),
+            Self::Username => ("MAX_USERNAME_LENGTH", 32),
+            Self::DeviceName => ("MAX_DEVICE_NAME_LENGTH", 64),
+            Self::ChannelTopic => ("MAX_CHANNEL_TOPIC_LENGTH", 1024),
+            Self::RoleName => ("MAX_ROLE_NAME_LENGTH", 32),
+        };
+        std::env::var(var)
+            .ok()
+            .and_then(|it| it.parse().ok())
+            .unwrap_or(default)
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Content => "content",
+            Self::ChannelName => "channel name",
+            Self::GuildName => "guild name",
+            Self::Username => "username",
+            Self::DeviceName => "device name",
+            Self::ChannelTopic => "channel topic",
+            Self::RoleName => "role name",
+        }
+    }
+}
+
+pub fn length(value: &str) -> usize {
+    value.graphemes(true).count()
+}
+
+/// The number of bytes a value with at most `max` grapheme clusters may have.
+pub fn max_bytes(max: usize) -> usize {
+    max.saturating_mul(MAX_GRAPHEME_BYTES)
+}
+
+/// Whether a value has more than `max` grapheme clusters, or more bytes than those may have.
+pub fn exceeds(value: &str, max: usize) -> bool {
+    value.len() > max_bytes(max) || length(value) > max
+}
+
+/// Truncates a value to at most `max` grapheme clusters, and the bytes those may have.
+pub fn truncate(value: &str, max: usize) -> String {
+    let max_bytes = max_bytes(max);
+    let mut bytes = 0;
+    value
+        .graphemes(true)
+        .take(max)
+        .take_while(|it| {
+            bytes += it.len();
+            bytes <= max_bytes
+        })
+        .collect()
+}
+
+/// Rejects message content that is over the length limit.
+///
+/// Empty content is allowed, since messages may consist of attachments only.
+pub fn content(value: &str) -> Result<(), ValidationError> {
+    text(Field::Content, value)
+}
+
+/// Rejects text that is longer than the maximum length of the field.
+pub fn text(field: Field, value: &str) -> Result<(), ValidationError> {
+    let max = field.max_length();
+    if value.len() > max_bytes(max) {
+        let max_bytes = max_bytes(max);
+        return Err(ValidationError::TooLarge { field, max_bytes });
+    }
+    if length(value) > max {
+        return Err(ValidationError::TooLong { field, max });
+    }
+    Ok(())
+}
+
+/// Rejects names that are blank once restricted. Names that are too long are truncated instead.
+pub fn name(field: Field, value: &str) -> Result<(), ValidationError> {
+    if RestrictedString::space(value, field).trim().is_empty() {
+        return Err(ValidationError::Empty { field });
+    }
+    Ok(())
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Empty { field } => write!(f, "The {} must not be empty", field.name()),
+            Self::TooLong { field, max } => {
+                write!(f, "The {} may be at most {max} characters", field.name())
+            }
+            Self::TooLarge { field, max_bytes } => {
+                write!(f, "The {} may be at most {max_bytes} bytes", field.name())
+            }
+        }
+    }
+}
+
+/// Serialized as the field, the reason it was rejected, and the limit it exceeds if any.
+impl Serialize for ValidationError {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let (field, reason, limit) = match self {
+            Self::Empty { field } => (field, "empty", None),
+            Self::TooLong { field, max } => (field, "too_long", Some(max)),
+            Self::TooLarge { field, max_bytes } => (field, "too_large", Some(max_bytes)),
+        };
+        let mut err = serializer.serialize_struct("ValidationError", 3)?;
+        err.serialize_field("field", field)?;
+        err.serialize_field("reason", reason)?;
+        err.serialize_field("limit", &limit)?;
+        err.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graphemes_count_once() {
+        assert_eq!(length("héllo"), 5);
+        assert_eq!(length("👍🏽👨‍👩‍👧"), 2);
+        assert!(!exceeds("👍🏽", 1));
+        assert!(exceeds("ab", 1));
+    }
+
+    #[test]
+    fn bytes_are_limited_per_grapheme() {
+        let zalgo = format!("a{}", "\u{301}".repeat(MAX_GRAPHEME_BYTES / 2));
+        assert_eq!(length(&zalgo), 1);
+        assert!(exceeds(&zalgo, 1));
+        assert!(!exceeds(&zalgo, 2));
+    }
+
+    #[test]
+    fn content_is_checked_in_bytes_first() {
+        let max = Field::Content.max_length();
+        assert!(content(&"a".repeat(max)).is_ok());
+        assert!(content("").is_ok());
+
+        let long = content(&"a".repeat(max + 1));
+        assert!(matches!(long, Err(ValidationError::TooLong { .. })));
+
+        let large = content(&"\u{301}".repeat(max_bytes(max)));
+        assert!(matches!(large, Err(ValidationError::TooLarge { .. })));
+    }
+
+    #[test]
+    fn truncate_respects_both_limits() {
+        assert_eq!(truncate("abcdef", 3), "abc");
+        assert_eq!(truncate("👍🏽👍🏽", 1), "👍🏽");
+
+        let zalgo = format!("a{}b", "\u{301}".repeat(MAX_GRAPHEME_BYTES));
+        assert_eq!(truncate(&zalgo, 2), "");
+        assert_eq!(truncate(&format!("b{zalgo}"), 2), "b");
+    }
+
+    #[test]
+    fn errors_serialize_with_their_limit() {
+        let err = ValidationError::TooLong {
+            field: Field::ChannelName,
+            max: 32,
+        };
+        assert_eq!(
+            serde_json::to_value(err).unwrap(),
+            serde_json::json!({"field": "channel_name", "reason": "too_long", "limit": 32})
+        );
+
+        let err = ValidationError::Empty {
+            field: Field::Username,
+        };
+        assert_eq!(
+            serde_json::to_value(err).unwrap(),
+            serde_json::json!({"field": "username", "reason": "empty", "limit": null})
+        );
+    }
+}
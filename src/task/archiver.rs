@@ -0,0 +1,47 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use crate::{
+    database,
+    structures::{
+        channel::{Channel, ChannelResponse},
+        client::ClientHolder,
+        event::Event,
+    },
+    with_lock,
+};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+
+/// Archives threads after they have been inactive for their `auto_archive_after`.
+pub async fn run(clients: ClientHolder) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let Ok(threads) = database().await.fetch_threads_to_archive().await else {
+            continue;
+        };
+
+        for thread in threads {
+            archive(thread, &clients).await;
+        }
+    }
+}
+
+async fn archive(thread: Channel, clients: &ClientHolder) {
+    let Ok(Some(thread)) = database().await.archive_thread(thread.id).await else {
+        return;
+    };
+
+    let users = thread.get_users().await.unwrap_or(vec![]);
+
+    with_lock!(clients).dispatch_users(
+        users,
+        &Event::ThreadUpdate(ChannelResponse::from_channel(thread)),
+    );
+}
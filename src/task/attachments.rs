@@ -0,0 +1,53 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::{Duration, SystemTime};
+
+use ulid::Ulid;
+
+use crate::{database, storage, structures::attachment::Attachment};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+const BATCH_SIZE: i64 = 100;
+/// How long an upload may wait to be sent in a message.
+const MAX_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// Deletes uploads that were never sent in a message.
+pub async fn run() {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let Some(cutoff) = SystemTime::now().checked_sub(MAX_AGE) else {
+            continue;
+        };
+        // Attachment ids are ULIDs, which sort by the time they were generated.
+        let before = Ulid::from_datetime(cutoff).to_string();
+
+        let Ok(attachments) = database()
+            .await
+            .fetch_stale_attachments(&before, BATCH_SIZE)
+            .await
+        else {
+            continue;
+        };
+
+        for attachment in attachments {
+            delete(attachment).await;
+        }
+    }
+}
+
+async fn delete(attachment: Attachment) {
+    // Only remove the file once the row is gone, since a message may have taken it meanwhile.
+    if database()
+        .await
+        .delete_unsent_attachment(&attachment.id)
+        .await
+        .unwrap_or(false)
+    {
+        let _ = storage().delete(&attachment.id).await;
+    }
+}
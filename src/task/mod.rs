@@ -0,0 +1,21 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Background tasks that run alongside the server.
+
+mod archiver;
+mod attachments;
+mod polls;
+mod scheduler;
+mod sweeper;
+
+use crate::structures::client::ClientHolder;
+
+pub fn spawn_all(clients: &ClientHolder) {
+    tokio::spawn(archiver::run(clients.clone()));
+    tokio::spawn(attachments::run());
+    tokio::spawn(polls::run(clients.clone()));
+    tokio::spawn(scheduler::run(clients.clone()));
+    tokio::spawn(sweeper::run(clients.clone()));
+}
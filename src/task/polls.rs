@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use crate::{
+    database,
+    structures::{client::ClientHolder, event::Event, message::Message, poll::PollTally},
+    with_lock,
+};
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// Freezes the results of polls once they close.
+pub async fn run(clients: ClientHolder) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let Ok(messages) = database().await.fetch_polls_to_close().await else {
+            continue;
+        };
+
+        for message in messages {
+            close(message, &clients).await;
+        }
+    }
+}
+
+async fn close(message: Message, clients: &ClientHolder) {
+    let database = database().await;
+
+    let Some(poll) = &message.poll else {
+        return;
+    };
+
+    let Ok(results) = database
+        .count_poll_votes(message.id.clone(), poll.options.len())
+        .await
+    else {
+        return;
+    };
+
+    let Ok(Some(message)) = database.close_poll(message.id.clone(), results).await else {
+        return;
+    };
+
+    let Ok(Some(tally)) = PollTally::fetch(&message).await else {
+        return;
+    };
+
+    let Ok(Some(channel)) = database.fetch_channel(message.channel_id.clone()).await else {
+        return;
+    };
+
+    with_lock!(clients).dispatch_users(
+        channel.get_users().await.unwrap_or(vec![]),
+        &Event::PollUpdate(tally),
+    );
+}
@@ -0,0 +1,99 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::{
+    database,
+    structures::{
+        client::ClientHolder,
+        emoji::GuildEmoji,
+        mention::Mentions,
+        message::{Message, MessageResponse},
+        scheduled_message::ScheduledMessage,
+        validation,
+    },
+};
+
+const INTERVAL: Duration = Duration::from_secs(5);
+
+/// Sends scheduled messages once they are due.
+pub async fn run(clients: ClientHolder) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let Ok(messages) = database().await.fetch_due_scheduled_messages().await else {
+            continue;
+        };
+
+        for message in messages {
+            send(message, &clients).await;
+        }
+    }
+}
+
+async fn send(scheduled: ScheduledMessage, clients: &ClientHolder) {
+    let database = database().await;
+
+    // Lookups that fail are retried on the next tick, so nothing is claimed before they succeed.
+    let Ok(channel) = database.fetch_channel(scheduled.channel_id.clone()).await else {
+        return;
+    };
+    let Some(channel) = channel else {
+        let _ = database.delete_scheduled_message(scheduled.id).await;
+        return;
+    };
+
+    let Some(users) = channel.get_users().await.option() else {
+        return;
+    };
+
+    // The author may have lost access since the message was scheduled.
+    if !users.contains(&scheduled.author_id) || !channel.can_send(&scheduled.author_id).await {
+        let _ = database.delete_scheduled_message(scheduled.id).await;
+        return;
+    }
+
+    let Ok(author) = database.fetch_user(&scheduled.author_id).await else {
+        return;
+    };
+    let Some(author) = author else {
+        let _ = database.delete_scheduled_message(scheduled.id).await;
+        return;
+    };
+
+    let next = scheduled.next_send_at(Utc::now());
+
+    if !database
+        .claim_scheduled_message(&scheduled, next.clone())
+        .await
+        .unwrap_or(false)
+    {
+        return;
+    }
+
+    // Resolved emoji are longer than their shortcodes, and may no longer fit.
+    let mut content = GuildEmoji::resolve_in(&scheduled.content, &channel).await;
+    if validation::content(&content).is_err() {
+        content = scheduled.content.clone();
+    }
+
+    let mentions = Mentions::parse(&content)
+        .resolve(&author.id, &users, &channel)
+        .await;
+
+    let Ok(message) = Message::new(channel.id.clone(), author.id.clone(), content)
+        .with_mentions(mentions)
+        .insert()
+        .await
+    else {
+        return;
+    };
+
+    let _ = MessageResponse::publish(message, channel, author, users, clients).await;
+}
@@ -0,0 +1,62 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::time::Duration;
+
+use crate::{
+    database,
+    structures::{
+        channel::Channel,
+        client::ClientHolder,
+        event::Event,
+        message::{Message, MessageDeleteResponse},
+    },
+    with_lock,
+};
+
+const INTERVAL: Duration = Duration::from_secs(5);
+const BATCH_SIZE: i64 = 100;
+
+/// Deletes ephemeral messages once they have expired.
+pub async fn run(clients: ClientHolder) {
+    let mut interval = tokio::time::interval(INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let Ok(messages) = database().await.fetch_expired_messages(BATCH_SIZE).await else {
+            continue;
+        };
+
+        for message in messages {
+            delete(message, &clients).await;
+        }
+    }
+}
+
+async fn delete(message: Message, clients: &ClientHolder) {
+    if !message.delete().await.unwrap_or(false) {
+        return;
+    }
+
+    Channel::archive_orphaned_threads(vec![message.id.clone()], clients).await;
+
+    let Ok(Some(channel)) = database()
+        .await
+        .fetch_channel(message.channel_id.clone())
+        .await
+    else {
+        return;
+    };
+
+    let resp = MessageDeleteResponse {
+        id: message.id,
+        channel_id: message.channel_id,
+    };
+
+    with_lock!(clients).dispatch_users(
+        channel.get_users().await.unwrap_or(vec![]),
+        &Event::MessageDelete(resp),
+    );
+}